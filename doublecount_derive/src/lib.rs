@@ -0,0 +1,258 @@
+//! Derive macros for the [doublecount](https://docs.rs/doublecount)
+//! accounting library.
+//!
+//! These are re-exported by `doublecount` when its `derive` feature
+//! is enabled.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Ident, LitInt, Token, Type,
+};
+
+/// A variant of the enum which `ActionTypeValueEnum` is being derived
+/// for, storing a single action.
+struct ActionVariant {
+    ident: Ident,
+    ty: Type,
+    priority: i64,
+    docs: Vec<Attribute>,
+}
+
+/// Derive `ActionTypeValueEnum` for an enum where each variant stores
+/// a single action, in order to use the enum to store the actions of
+/// a `Program`.
+///
+/// This generates:
+///
+/// + An enum of action types, with a variant of the same name for
+///   each variant of the derived enum. Its name is the name of the
+///   derived enum with the `Value` suffix removed (or with a `Type`
+///   suffix added if it does not end in `Value`), and can be set
+///   using `#[action_type(name = MyActionType)]` on the derived enum.
+///   The action types are ordered by their priority, which is used
+///   to sort actions which occur on the same day, and can be listed
+///   using the generated `iterator()` method.
+/// + An implementation of `ActionTypeValueEnum` for the derived enum.
+/// + Implementations of `ActionTypeFor` for the derived enum and for
+///   each stored action.
+/// + An implementation of `From` for the derived enum from each
+///   stored action.
+///
+/// The priority of a variant is set using `#[action(priority = n)]`,
+/// where actions with a lower priority are performed first. The
+/// default priority is `0`, and variants with the same priority are
+/// ordered in the same order as they are declared.
+///
+/// # Example
+///
+/// ```
+/// use doublecount::{EditAccountStatus, Transaction};
+/// use doublecount_derive::ActionTypeValueEnum;
+///
+/// #[derive(Debug, Clone, PartialEq, ActionTypeValueEnum)]
+/// enum MyActionTypeValue {
+///     Transaction(Transaction),
+///     #[action(priority = -1)]
+///     EditAccountStatus(EditAccountStatus),
+/// }
+///
+/// let action_types: Vec<&MyActionType> = MyActionType::iterator().collect();
+/// assert_eq!(
+///     vec![&MyActionType::EditAccountStatus, &MyActionType::Transaction],
+///     action_types
+/// );
+/// ```
+#[proc_macro_derive(ActionTypeValueEnum, attributes(action_type, action))]
+pub fn derive_action_type_value_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_action_type_value_enum(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_action_type_value_enum(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "ActionTypeValueEnum can only be derived for an enum",
+            ))
+        }
+    };
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "ActionTypeValueEnum cannot be derived for an enum with generic parameters",
+        ));
+    }
+
+    let mut variants: Vec<ActionVariant> = Vec::new();
+    for variant in &data.variants {
+        let ty = match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => fields.unnamed[0].ty.clone(),
+            _ => return Err(Error::new_spanned(
+                variant,
+                "each variant needs to store a single action, such as `Transaction(Transaction)`",
+            )),
+        };
+
+        variants.push(ActionVariant {
+            ident: variant.ident.clone(),
+            ty,
+            priority: parse_priority(&variant.attrs)?,
+            docs: variant
+                .attrs
+                .iter()
+                .filter(|attr| attr.path().is_ident("doc"))
+                .cloned()
+                .collect(),
+        });
+    }
+
+    if variants.is_empty() {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "ActionTypeValueEnum cannot be derived for an enum without variants",
+        ));
+    }
+
+    let vis = &input.vis;
+    let value_ident = &input.ident;
+    let type_ident = parse_action_type_name(&input)?;
+
+    // a stable sort, so variants with the same priority remain in
+    // the order that they are declared
+    let mut ordered: Vec<&ActionVariant> = variants.iter().collect();
+    ordered.sort_by_key(|variant| variant.priority);
+
+    let ordered_idents: Vec<&Ident> = ordered.iter().map(|variant| &variant.ident).collect();
+    let ordered_docs: Vec<&Vec<Attribute>> = ordered.iter().map(|variant| &variant.docs).collect();
+    let num_action_types = ordered.len();
+
+    let idents: Vec<&Ident> = variants.iter().map(|variant| &variant.ident).collect();
+    let tys: Vec<&Type> = variants.iter().map(|variant| &variant.ty).collect();
+
+    let type_doc = format!(
+        "A representation of what type of action is stored in a [{0}]({0}), \
+         in order of its priority when sorting actions on the same day.",
+        value_ident
+    );
+    let iterator_doc = format!(
+        "Return an iterator over all available [{0}]({0}) variants.",
+        type_ident
+    );
+
+    Ok(quote! {
+        #[doc = #type_doc]
+        #[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Hash, Clone)]
+        #vis enum #type_ident {
+            #(
+                #(#ordered_docs)*
+                #ordered_idents,
+            )*
+        }
+
+        impl #type_ident {
+            #[doc = #iterator_doc]
+            pub fn iterator() -> ::std::slice::Iter<'static, #type_ident> {
+                static ACTION_TYPES: [#type_ident; #num_action_types] = [
+                    #(#type_ident::#ordered_idents),*
+                ];
+                ACTION_TYPES.iter()
+            }
+        }
+
+        impl ::doublecount::ActionTypeValueEnum<#type_ident> for #value_ident {
+            fn as_action(&self) -> &dyn ::doublecount::Action<#type_ident, #value_ident> {
+                match self {
+                    #(#value_ident::#idents(action) => action,)*
+                }
+            }
+        }
+
+        impl ::doublecount::ActionTypeFor<#type_ident> for #value_ident {
+            fn action_type(&self) -> #type_ident {
+                match self {
+                    #(#value_ident::#idents(_) => #type_ident::#idents,)*
+                }
+            }
+        }
+
+        #(
+            impl ::doublecount::ActionTypeFor<#type_ident> for #tys {
+                fn action_type(&self) -> #type_ident {
+                    #type_ident::#idents
+                }
+            }
+
+            impl ::std::convert::From<#tys> for #value_ident {
+                fn from(action: #tys) -> Self {
+                    #value_ident::#idents(action)
+                }
+            }
+        )*
+    })
+}
+
+/// Parse the name of the action type enum from the
+/// `#[action_type(name = ...)]` attribute, or derive it from the name
+/// of the enum.
+fn parse_action_type_name(input: &DeriveInput) -> syn::Result<Ident> {
+    let mut name: Option<Ident> = None;
+
+    for attr in &input.attrs {
+        if !attr.path().is_ident("action_type") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported action_type attribute, expected `name`"))
+            }
+        })?;
+    }
+
+    Ok(name.unwrap_or_else(|| {
+        let value_name = input.ident.to_string();
+        match value_name.strip_suffix("Value") {
+            Some(type_name) if !type_name.is_empty() => format_ident!("{}", type_name),
+            _ => format_ident!("{}Type", value_name),
+        }
+    }))
+}
+
+/// Parse the priority of a variant from the `#[action(priority = n)]`
+/// attribute, which defaults to `0`.
+fn parse_priority(attrs: &[Attribute]) -> syn::Result<i64> {
+    let mut priority: i64 = 0;
+
+    for attr in attrs {
+        if !attr.path().is_ident("action") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("priority") {
+                let value = meta.value()?;
+                let negative = value.parse::<Option<Token![-]>>()?.is_some();
+                let literal: LitInt = value.parse()?;
+                let magnitude: i64 = literal.base10_parse()?;
+                priority = if negative { -magnitude } else { magnitude };
+                Ok(())
+            } else {
+                Err(meta.error("unsupported action attribute, expected `priority`"))
+            }
+        })?;
+    }
+
+    Ok(priority)
+}
@@ -0,0 +1,139 @@
+use chrono::NaiveDate;
+use commodity::{Commodity, CommodityType};
+use doublecount::{
+    Account, AccountStatus, AccountingError, Action, ActionTypeFor, EditAccountStatus, Program,
+    ProgramState, Transaction,
+};
+use doublecount_derive::ActionTypeValueEnum;
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// A custom action which adds a note to the program, and does
+/// nothing when performed.
+#[derive(Debug, Clone, PartialEq)]
+struct Note {
+    date: NaiveDate,
+    text: String,
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Note: {}", self.text)
+    }
+}
+
+impl<AT, ATV> Action<AT, ATV> for Note
+where
+    ATV: doublecount::ActionTypeValueEnum<AT>,
+{
+    fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    fn perform(&self, _program_state: &mut ProgramState<AT, ATV>) -> Result<(), AccountingError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, ActionTypeValueEnum)]
+enum CustomActionTypeValue {
+    /// A transaction.
+    Transaction(Transaction),
+    #[action(priority = 1)]
+    Note(Note),
+    #[action(priority = -1)]
+    EditAccountStatus(EditAccountStatus),
+}
+
+#[derive(Debug, Clone, PartialEq, ActionTypeValueEnum)]
+#[action_type(name = RenamedType)]
+enum RenamedActions {
+    Note(Note),
+    Transaction(Transaction),
+}
+
+#[test]
+fn action_type_order() {
+    let action_types: Vec<&CustomActionType> = CustomActionType::iterator().collect();
+    assert_eq!(
+        vec![
+            &CustomActionType::EditAccountStatus,
+            &CustomActionType::Transaction,
+            &CustomActionType::Note,
+        ],
+        action_types
+    );
+    assert!(CustomActionType::EditAccountStatus < CustomActionType::Note);
+
+    let note = RenamedActions::from(Note {
+        date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+        text: String::from("Renamed"),
+    });
+    assert_eq!(RenamedType::Note, note.action_type());
+
+    let action_types: Vec<&RenamedType> = RenamedType::iterator().collect();
+    assert_eq!(
+        vec![&RenamedType::Note, &RenamedType::Transaction],
+        action_types
+    );
+}
+
+#[test]
+fn execute_program() {
+    let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+    let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+    let account1 = Rc::from(Account::new_with_id(Some("Account 1"), aud.id, None));
+    let account2 = Rc::from(Account::new_with_id(Some("Account 2"), aud.id, None));
+    let accounts = vec![account1.clone(), account2.clone()];
+
+    let note = Note {
+        date,
+        text: String::from("Opened the accounts"),
+    };
+    assert_eq!(CustomActionType::Note, note.action_type());
+
+    let actions: Vec<Rc<CustomActionTypeValue>> = vec![
+        Rc::new(note.into()),
+        Rc::new(
+            Transaction::new_simple::<String>(
+                None,
+                date,
+                account1.id,
+                account2.id,
+                Commodity::from_str("2.52 AUD").unwrap(),
+                None,
+            )
+            .into(),
+        ),
+        Rc::new(EditAccountStatus::new(account1.id, AccountStatus::Open, date).into()),
+        Rc::new(EditAccountStatus::new(account2.id, AccountStatus::Open, date).into()),
+    ];
+
+    let program: Program<CustomActionType, CustomActionTypeValue> = Program::new(actions);
+    let action_types: Vec<CustomActionType> = program
+        .actions
+        .iter()
+        .map(|action| action.action_type())
+        .collect();
+    assert_eq!(
+        vec![
+            CustomActionType::EditAccountStatus,
+            CustomActionType::EditAccountStatus,
+            CustomActionType::Transaction,
+            CustomActionType::Note,
+        ],
+        action_types
+    );
+
+    let mut program_state = ProgramState::new(&accounts, AccountStatus::Closed);
+    program_state.execute_program(&program).unwrap();
+
+    assert_eq!(
+        Commodity::from_str("2.52 AUD").unwrap(),
+        program_state
+            .get_account_state(&account2.id)
+            .unwrap()
+            .amount
+    );
+}
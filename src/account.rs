@@ -1,7 +1,11 @@
+use super::{AccountConstraint, AccountingError, BookingMethod, Lot};
 use arrayvec::ArrayString;
+use chrono::NaiveDate;
 use commodity::{Commodity, CommodityTypeID};
 use nanoid::nanoid;
 use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::rc::Rc;
 
 #[cfg(feature = "serde-support")]
@@ -11,6 +15,9 @@ use serde::{Deserialize, Serialize};
 const ACCOUNT_ID_LENGTH: usize = 20;
 
 /// The status of an [Account](Account) stored within an [AccountState](AccountState).
+///
+/// The status determines in which [PostingDirection](PostingDirection)s
+/// a [Transaction](crate::Transaction) can modify the account.
 #[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum AccountStatus {
@@ -18,7 +25,80 @@ pub enum AccountStatus {
     Open,
     /// The account is closed
     Closed,
+    /// The account is frozen (e.g. while it is under review), no
+    /// amounts can be posted to it
+    Frozen,
+    /// Only incoming (positive) amounts can be posted to the account
+    ReceiveOnly,
+    /// Only outgoing (negative) amounts can be posted to the account
+    SendOnly,
+    /// The account is being wound down before it is closed, only
+    /// amounts which move its balance towards zero can be posted to
+    /// it
+    PendingClose,
 }
+
+impl AccountStatus {
+    /// Returns true if an `amount` can be posted to an account with
+    /// this status, which currently has the given `balance`.
+    pub fn permits(&self, amount: Commodity, balance: Commodity) -> bool {
+        let direction = PostingDirection::of(amount);
+        match self {
+            AccountStatus::Open => true,
+            AccountStatus::Closed | AccountStatus::Frozen => false,
+            AccountStatus::ReceiveOnly => direction == PostingDirection::Incoming,
+            AccountStatus::SendOnly => direction == PostingDirection::Outgoing,
+            AccountStatus::PendingClose => {
+                amount.value.is_zero()
+                    || (!balance.value.is_zero()
+                        && balance.value.is_sign_negative() != amount.value.is_sign_negative()
+                        && amount.value.abs() <= balance.value.abs())
+            }
+        }
+    }
+}
+
+/// The direction of an amount posted to an account by a
+/// [Transaction](crate::Transaction).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PostingDirection {
+    /// A positive (or zero) amount, increasing the balance of the
+    /// account
+    Incoming,
+    /// A negative amount, decreasing the balance of the account
+    Outgoing,
+}
+
+impl PostingDirection {
+    /// The direction of posting the `amount` to an account.
+    pub fn of(amount: Commodity) -> PostingDirection {
+        if amount.value.is_sign_negative() && !amount.value.is_zero() {
+            PostingDirection::Outgoing
+        } else {
+            PostingDirection::Incoming
+        }
+    }
+}
+
+impl fmt::Display for PostingDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PostingDirection::Incoming => write!(f, "incoming"),
+            PostingDirection::Outgoing => write!(f, "outgoing"),
+        }
+    }
+}
+
+/// A change to the [AccountStatus](AccountStatus) of an
+/// [AccountState](AccountState), recorded in its `status_history`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AccountStatusChange {
+    /// The new status of the account.
+    pub status: AccountStatus,
+    /// The date that the status was changed.
+    pub date: NaiveDate,
+}
+
 /// The type to use for the id of [Account](Account)s.
 pub type AccountID = ArrayString<[u8; ACCOUNT_ID_LENGTH]>;
 
@@ -41,6 +121,83 @@ pub struct Account {
 
     /// The category that this account part of
     pub category: Option<AccountCategory>,
+
+    /// The id of the parent of this account, if it is part of a
+    /// hierarchy of accounts.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub parent_id: Option<AccountID>,
+
+    /// The method used to book reductions of [Lot](Lot)s held in this
+    /// account. If this is `None`, lots are not tracked for this
+    /// account.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub booking_method: Option<BookingMethod>,
+
+    /// Constraints which are checked whenever a transaction modifies
+    /// this account.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub constraints: Vec<AccountConstraint>,
+
+    /// Whether this account is an inventory, which can hold amounts
+    /// of commodity types other than its `commodity_type_id` (such as
+    /// a brokerage account holding shares and cash). These are
+    /// stored in the `inventory` of its [AccountState](AccountState).
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub inventory: bool,
+
+    /// The date that this account was opened, if known.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub open_date: Option<NaiveDate>,
+
+    /// The date that this account was closed, if it has been.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub close_date: Option<NaiveDate>,
+
+    /// The name of the institution which holds this account.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub institution: Option<String>,
+
+    /// The number used by the institution to identify this account.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub account_number: Option<String>,
+
+    /// Free form notes about this account.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub notes: Option<String>,
+
+    /// Arbitrary key/value metadata associated with this account.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl Account {
@@ -79,8 +236,130 @@ impl Account {
             name: name.map(|s| s.into()),
             commodity_type_id,
             category,
+            parent_id: None,
+            booking_method: None,
+            constraints: Vec::new(),
+            inventory: false,
+            open_date: None,
+            close_date: None,
+            institution: None,
+            account_number: None,
+            notes: None,
+            metadata: BTreeMap::new(),
         }
     }
+
+    /// Track the [Lot](Lot)s held in this account, and book
+    /// reductions using the given `booking_method`.
+    pub fn with_booking_method(mut self, booking_method: BookingMethod) -> Account {
+        self.booking_method = Some(booking_method);
+        self
+    }
+
+    /// Add a `constraint` which is checked whenever a transaction
+    /// modifies this account.
+    pub fn with_constraint(mut self, constraint: AccountConstraint) -> Account {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Make this account an inventory, which can hold amounts of
+    /// multiple commodity types.
+    pub fn with_inventory(mut self) -> Account {
+        self.inventory = true;
+        self
+    }
+
+    /// Set the id of the parent of this account.
+    pub fn with_parent_id(mut self, parent_id: AccountID) -> Account {
+        self.parent_id = Some(parent_id);
+        self
+    }
+
+    /// Set the date that this account was opened.
+    pub fn with_open_date(mut self, open_date: NaiveDate) -> Account {
+        self.open_date = Some(open_date);
+        self
+    }
+
+    /// Set the date that this account was closed.
+    pub fn with_close_date(mut self, close_date: NaiveDate) -> Account {
+        self.close_date = Some(close_date);
+        self
+    }
+
+    /// Set the name of the institution which holds this account.
+    pub fn with_institution<S: Into<String>>(mut self, institution: S) -> Account {
+        self.institution = Some(institution.into());
+        self
+    }
+
+    /// Set the number used by the institution to identify this
+    /// account.
+    pub fn with_account_number<S: Into<String>>(mut self, account_number: S) -> Account {
+        self.account_number = Some(account_number.into());
+        self
+    }
+
+    /// Set the notes about this account.
+    pub fn with_notes<S: Into<String>>(mut self, notes: S) -> Account {
+        self.notes = Some(notes.into());
+        self
+    }
+
+    /// Add a `key`/`value` pair to the metadata of this account,
+    /// replacing any existing value for the `key`.
+    pub fn with_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Account {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Get the metadata value associated with the `key`.
+    pub fn get_metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+
+    /// Whether this account is open on the given `date` according to
+    /// its declared `open_date` and `close_date`. An account is
+    /// considered to be open from its `open_date` (inclusive) until
+    /// its `close_date` (exclusive), and either bound may be absent.
+    pub fn is_open_on(&self, date: NaiveDate) -> bool {
+        self.open_date.map_or(true, |open_date| date >= open_date)
+            && self.close_date.map_or(true, |close_date| date < close_date)
+    }
+}
+
+/// The ids of the account with the given `account_id`, and of all its
+/// descendants within `accounts` (according to their
+/// [parent_id](Account::parent_id)).
+pub fn account_subtree_ids<'a, I>(account_id: AccountID, accounts: I) -> HashSet<AccountID>
+where
+    I: IntoIterator<Item = &'a Account>,
+{
+    let accounts: Vec<&Account> = accounts.into_iter().collect();
+    let mut subtree: HashSet<AccountID> = HashSet::new();
+    subtree.insert(account_id);
+
+    // repeatedly add the children of accounts already in the
+    // subtree, until no more are found
+    loop {
+        let children: Vec<AccountID> = accounts
+            .iter()
+            .filter(|account| {
+                !subtree.contains(&account.id)
+                    && account
+                        .parent_id
+                        .map_or(false, |parent_id| subtree.contains(&parent_id))
+            })
+            .map(|account| account.id)
+            .collect();
+
+        if children.is_empty() {
+            return subtree;
+        }
+
+        subtree.extend(children);
+    }
 }
 
 impl PartialEq for Account {
@@ -98,8 +377,21 @@ pub struct AccountState {
     /// The amount of the commodity currently stored in this account
     pub amount: Commodity,
 
+    /// The amounts of commodity types other than the account's
+    /// `commodity_type_id` currently stored in this account, if the
+    /// account is an [inventory](Account::inventory).
+    pub inventory: BTreeMap<CommodityTypeID, Commodity>,
+
     /// The status of this account (open/closed/etc...)
     pub status: AccountStatus,
+
+    /// The [Lot](Lot)s held in this account, if the account has a
+    /// `booking_method`.
+    pub lots: Vec<Lot>,
+
+    /// The changes made to the `status` of this account (including
+    /// reopening a closed account), in the order they were made.
+    pub status_history: Vec<AccountStatusChange>,
 }
 
 impl AccountState {
@@ -108,16 +400,112 @@ impl AccountState {
         AccountState {
             account,
             amount,
+            inventory: BTreeMap::new(),
             status,
+            lots: Vec::new(),
+            status_history: Vec::new(),
+        }
+    }
+
+    /// The balance of the commodity type with the id `type_id` stored
+    /// in this account.
+    pub fn balance(&self, type_id: CommodityTypeID) -> Commodity {
+        if self.amount.type_id == type_id {
+            self.amount
+        } else {
+            self.inventory
+                .get(&type_id)
+                .copied()
+                .unwrap_or_else(|| Commodity::zero(type_id))
+        }
+    }
+
+    /// The balances of all the commodity types stored in this
+    /// account, starting with the `amount`, followed by the
+    /// `inventory`.
+    pub fn balances(&self) -> Vec<Commodity> {
+        let mut balances = vec![self.amount];
+        balances.extend(self.inventory.values().copied());
+        balances
+    }
+
+    /// Check that the `status` of this account permits posting the
+    /// `amount` to it, otherwise returning an
+    /// [InvalidAccountStatus](AccountingError::InvalidAccountStatus)
+    /// error.
+    pub fn check_posting(&self, amount: Commodity) -> Result<(), AccountingError> {
+        if self.status.permits(amount, self.balance(amount.type_id)) {
+            Ok(())
+        } else {
+            Err(AccountingError::InvalidAccountStatus {
+                account_id: self.account.id,
+                status: self.status,
+                direction: PostingDirection::of(amount),
+            })
+        }
+    }
+
+    /// Add an `amount` to the balance of its commodity type in this
+    /// account. This fails if the account is not an
+    /// [inventory](Account::inventory) and the `amount` is not of the
+    /// account's commodity type.
+    pub fn post(&mut self, amount: &Commodity) -> Result<(), AccountingError> {
+        if amount.type_id == self.amount.type_id || !self.account.inventory {
+            self.amount = self.amount.add(amount)?;
+            return Ok(());
+        }
+
+        let balance = self.balance(amount.type_id).add(amount)?;
+        if balance.value.is_zero() {
+            self.inventory.remove(&amount.type_id);
+        } else {
+            self.inventory.insert(amount.type_id, balance);
+        }
+        Ok(())
+    }
+
+    /// Set the `status` of this account on the given `date`, and
+    /// record the change in the `status_history`.
+    pub fn set_status(&mut self, status: AccountStatus, date: NaiveDate) {
+        self.status = status;
+        self.status_history
+            .push(AccountStatusChange { status, date });
+    }
+
+    /// The date that this account was most recently opened, if it was
+    /// opened using [set_status()](AccountState::set_status()).
+    pub fn opened_on(&self) -> Option<NaiveDate> {
+        self.status_history
+            .iter()
+            .rev()
+            .find(|change| change.status == AccountStatus::Open)
+            .map(|change| change.date)
+    }
+
+    /// The date that this account was closed, if it is currently
+    /// [Closed](AccountStatus::Closed) and was closed using
+    /// [set_status()](AccountState::set_status()).
+    pub fn closed_on(&self) -> Option<NaiveDate> {
+        match self.status {
+            AccountStatus::Closed => self
+                .status_history
+                .last()
+                .filter(|change| change.status == AccountStatus::Closed)
+                .map(|change| change.date),
+            _ => None,
         }
     }
 
     /// Open this account, set the `status` to [Open](AccountStatus::Open)
+    /// without recording the change in the `status_history`.
+    #[deprecated(note = "use set_status() to record the date that the account was opened")]
     pub fn open(&mut self) {
         self.status = AccountStatus::Open;
     }
 
-    // Close this account, set the `status` to [Closed](AccountStatus::Closed)
+    /// Close this account, set the `status` to [Closed](AccountStatus::Closed)
+    /// without recording the change in the `status_history`.
+    #[deprecated(note = "use set_status() to record the date that the account was closed")]
     pub fn close(&mut self) {
         self.status = AccountStatus::Closed;
     }
@@ -126,6 +514,11 @@ impl AccountState {
         self.account == other.account
             && self.status == other.status
             && self.amount.eq_approx(other.amount, epsilon)
+            && self.inventory.len() == other.inventory.len()
+            && self
+                .inventory
+                .iter()
+                .all(|(type_id, amount)| amount.eq_approx(other.balance(*type_id), epsilon))
     }
 }
 
@@ -134,6 +527,7 @@ impl AccountState {
 mod serde_tests {
     use super::Account;
     use super::AccountID;
+    use chrono::NaiveDate;
     use commodity::CommodityTypeID;
     use std::str::FromStr;
 
@@ -160,4 +554,34 @@ mod serde_tests {
         assert_eq!(reference_account, account);
         insta::assert_json_snapshot!(account);
     }
+
+    #[test]
+    fn account_details_serde() {
+        use serde_json;
+
+        let json = r#"{
+  "id": "ABCDEFGHIJKLMNOPQRST",
+  "name": "Test Account",
+  "commodity_type_id": "USD",
+  "category": "Asset",
+  "open_date": "2020-01-01",
+  "close_date": "2021-01-01",
+  "institution": "Test Bank",
+  "account_number": "123-456 7890",
+  "notes": "Test notes",
+  "metadata": {
+    "colour": "blue"
+  }
+}"#;
+
+        let account: Account = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()),
+            account.open_date
+        );
+        assert_eq!(Some("Test Bank"), account.institution.as_deref());
+        assert_eq!(Some("blue"), account.get_metadata("colour"));
+        insta::assert_json_snapshot!(account);
+    }
 }
@@ -1,8 +1,13 @@
-use super::{AccountID, AccountStatus, AccountingError, ProgramState};
-use chrono::NaiveDate;
+use super::{
+    account_subtree_ids, apply_lot_reductions, book_lots, AccountID, AccountStatus,
+    AccountingError, ActionOrdering, ConstraintMode, ConstraintViolation, DayCount,
+    DefaultOrdering, Disposal, InterestMethod, Lot, ProgramState, RoundingPolicy,
+};
+use chrono::{NaiveDate, NaiveTime};
 use commodity::exchange_rate::ExchangeRate;
-use commodity::Commodity;
+use commodity::{Commodity, CommodityTypeID};
 use rust_decimal::{prelude::Zero, Decimal};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::rc::Rc;
 use std::{marker::PhantomData, slice};
@@ -20,21 +25,49 @@ pub enum ActionType {
     /// other actions on the same day may depend on this already having
     /// been executed.
     EditAccountStatus,
+    /// An [Action](Action) to record the price of a
+    /// [CommodityType](commodity::CommodityType) in the
+    /// [PriceDatabase](crate::PriceDatabase). Represented by the
+    /// [Price](Price) struct.
+    ///
+    /// This action has a high priority when being sorted, so that
+    /// prices are available to other actions on the same day.
+    Price,
     /// An [Action](Action) to assert the current balance of an account while
     /// a [Program](super::Program) is being executed. Represented by a
     /// [BalanceAssertion](BalanceAssertion) struct.
     BalanceAssertion,
+    /// An [Action](Action) to pad an account with a balancing
+    /// transaction, so that its next [BalanceAssertion](BalanceAssertion)
+    /// passes. Represented by the [Pad](Pad) struct.
+    Pad,
     /// A [Action](Action) to perform a transaction between [Account](crate::Account)s.
     /// Represented by the [Transaction](Transaction) struct.
     Transaction,
+    /// An [Action](Action) to accrue interest on the daily balance of
+    /// an [Account](crate::Account) over a period. Represented by the
+    /// [AccrueInterest](AccrueInterest) struct.
+    ///
+    /// This action has the lowest priority when being sorted, so that
+    /// interest is posted after the other actions without a time of
+    /// day on the same date. The [DefaultOrdering](crate::DefaultOrdering)
+    /// compares the time of day before the action type, and this
+    /// action has no time of day, so it is performed *before* any
+    /// [Transaction](Transaction)s on the same date which have a
+    /// time. Use a custom [ActionOrdering](crate::ActionOrdering) if
+    /// interest needs to be posted after those.
+    AccrueInterest,
 }
 
 impl ActionTypeFor<ActionType> for ActionTypeValue {
     fn action_type(&self) -> ActionType {
         match self {
             ActionTypeValue::EditAccountStatus(_) => ActionType::EditAccountStatus,
+            ActionTypeValue::Price(_) => ActionType::Price,
             ActionTypeValue::BalanceAssertion(_) => ActionType::BalanceAssertion,
+            ActionTypeValue::Pad(_) => ActionType::Pad,
             ActionTypeValue::Transaction(_) => ActionType::Transaction,
+            ActionTypeValue::AccrueInterest(_) => ActionType::AccrueInterest,
         }
     }
 }
@@ -42,10 +75,13 @@ impl ActionTypeFor<ActionType> for ActionTypeValue {
 impl ActionType {
     /// Return an iterator over all available [ActionType](ActionType) variants.
     pub fn iterator() -> slice::Iter<'static, ActionType> {
-        static ACTION_TYPES: [ActionType; 3] = [
+        static ACTION_TYPES: [ActionType; 6] = [
             ActionType::EditAccountStatus,
+            ActionType::Price,
             ActionType::BalanceAssertion,
+            ActionType::Pad,
             ActionType::Transaction,
+            ActionType::AccrueInterest,
         ];
         ACTION_TYPES.iter()
     }
@@ -69,16 +105,22 @@ pub trait ActionTypeValueEnum<AT> {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ActionTypeValue {
     EditAccountStatus(EditAccountStatus),
+    Price(Price),
     BalanceAssertion(BalanceAssertion),
+    Pad(Pad),
     Transaction(Transaction),
+    AccrueInterest(AccrueInterest),
 }
 
 impl<AT> ActionTypeValueEnum<AT> for ActionTypeValue {
     fn as_action(&self) -> &dyn Action<AT, ActionTypeValue> {
         match self {
             ActionTypeValue::EditAccountStatus(action) => action,
+            ActionTypeValue::Price(action) => action,
             ActionTypeValue::BalanceAssertion(action) => action,
+            ActionTypeValue::Pad(action) => action,
             ActionTypeValue::Transaction(action) => action,
+            ActionTypeValue::AccrueInterest(action) => action,
         }
     }
 }
@@ -89,18 +131,36 @@ impl From<EditAccountStatus> for ActionTypeValue {
     }
 }
 
+impl From<Price> for ActionTypeValue {
+    fn from(action: Price) -> Self {
+        ActionTypeValue::Price(action)
+    }
+}
+
 impl From<BalanceAssertion> for ActionTypeValue {
     fn from(action: BalanceAssertion) -> Self {
         ActionTypeValue::BalanceAssertion(action)
     }
 }
 
+impl From<Pad> for ActionTypeValue {
+    fn from(action: Pad) -> Self {
+        ActionTypeValue::Pad(action)
+    }
+}
+
 impl From<Transaction> for ActionTypeValue {
     fn from(action: Transaction) -> Self {
         ActionTypeValue::Transaction(action)
     }
 }
 
+impl From<AccrueInterest> for ActionTypeValue {
+    fn from(action: AccrueInterest) -> Self {
+        ActionTypeValue::AccrueInterest(action)
+    }
+}
+
 /// Obtain the concrete action type for an action.
 pub trait ActionTypeFor<AT> {
     /// What type of action is being performed.
@@ -112,12 +172,67 @@ pub trait Action<AT, ATV>: fmt::Display + fmt::Debug {
     /// The date/time (in the account history) that the action was performed.
     fn date(&self) -> NaiveDate;
 
+    /// The time of day that the action was performed, if known. Used
+    /// to order actions which occur on the same date.
+    fn time(&self) -> Option<NaiveTime> {
+        None
+    }
+
+    /// An explicit sequence number for the action, used to order
+    /// actions which occur at the same date and time.
+    fn sequence(&self) -> Option<u32> {
+        None
+    }
+
     /// Perform the action to mutate the [ProgramState](ProgramState).
     fn perform(&self, program_state: &mut ProgramState<AT, ATV>) -> Result<(), AccountingError>;
+
+    /// Returns a reference to this action if it is a
+    /// [BalanceAssertion](BalanceAssertion). This is used by actions
+    /// such as [Pad](Pad) which need to look ahead in the
+    /// [Program](crate::Program) for assertions.
+    fn as_balance_assertion(&self) -> Option<&BalanceAssertion> {
+        None
+    }
+
+    /// Returns a reference to this action if it is a
+    /// [Transaction](Transaction). This is used to query actions by
+    /// their tags and description.
+    fn as_transaction(&self) -> Option<&Transaction> {
+        None
+    }
+
+    /// The ids of the [Account](crate::Account)s which this action
+    /// refers to.
+    fn account_ids(&self) -> Vec<AccountID> {
+        Vec::new()
+    }
+
+    /// The amounts which are specified by this action. Amounts which
+    /// are only calculated when the action is performed are not
+    /// included.
+    fn amounts(&self) -> Vec<Commodity> {
+        Vec::new()
+    }
+
+    /// Whether this action reads the history of account balances
+    /// using [ProgramState::balance_on()](ProgramState::balance_on()),
+    /// in which case the history is recorded while the program
+    /// containing the action is being executed.
+    fn needs_balance_history(&self) -> bool {
+        false
+    }
 }
 
-/// A way to sort [Action](Action)s by their date, and then by the
-/// priority of their [ActionType](ActionType).
+/// A way to sort [Action](Action)s by their date, then by their
+/// [time()](Action::time()), then by their
+/// [sequence()](Action::sequence()), and then by the priority of their
+/// [ActionType](ActionType).
+///
+/// Actions without a time are ordered before actions with a time on
+/// the same date, and actions without a sequence number are ordered
+/// before actions with a sequence number at the same time. This is
+/// the same order as the [DefaultOrdering](DefaultOrdering).
 ///
 /// # Example
 /// ```
@@ -141,7 +256,7 @@ impl<AT, ATV> ActionOrder<AT, ATV> {
     pub fn new(action_value: Rc<ATV>) -> Self {
         Self {
             action_value,
-            action_type: PhantomData::default(),
+            action_type: PhantomData,
         }
     }
 }
@@ -156,6 +271,8 @@ where
         let other_action = other.action_value.as_action();
         self.action_value.action_type() == other.action_value.action_type()
             && self_action.date() == other_action.date()
+            && self_action.time() == other_action.time()
+            && self_action.sequence() == other_action.sequence()
     }
 }
 
@@ -172,18 +289,7 @@ where
     ATV: ActionTypeValueEnum<AT> + ActionTypeFor<AT>,
 {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let self_action = self.action_value.as_action();
-        let other_action = other.action_value.as_action();
-        self_action
-            .date()
-            .partial_cmp(&other_action.date())
-            .map(|date_order| {
-                date_order.then(
-                    self.action_value
-                        .action_type()
-                        .cmp(&other.action_value.action_type()),
-                )
-            })
+        Some(self.cmp(other))
     }
 }
 
@@ -193,12 +299,10 @@ where
     ATV: ActionTypeValueEnum<AT> + ActionTypeFor<AT>,
 {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let self_action = self.action_value.as_action();
-        let other_action = other.action_value.as_action();
-        self_action.date().cmp(&other_action.date()).then(
-            self.action_value
-                .action_type()
-                .cmp(&other.action_value.action_type()),
+        ActionOrdering::<AT, ATV>::compare(
+            &DefaultOrdering,
+            &self.action_value,
+            &other.action_value,
         )
     }
 }
@@ -218,11 +322,50 @@ pub struct Transaction {
     pub description: Option<String>,
     /// The date that the transaction occurred.
     pub date: NaiveDate,
+    /// The time of day that the transaction occurred, if known.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub time: Option<NaiveTime>,
+    /// An explicit sequence number used to order this transaction
+    /// relative to other actions at the same date and time.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub sequence: Option<u32>,
     /// Elements which compose this transaction.
     ///
     /// See [Transaction](Transaction) for more information about the
     /// constraints which apply to this field.
     pub elements: Vec<TransactionElement>,
+    /// The payee (or payer) of this transaction.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub payee: Option<String>,
+    /// Tags used to categorize this transaction.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "BTreeSet::is_empty")
+    )]
+    pub tags: BTreeSet<String>,
+    /// Links used to group this transaction with other related
+    /// transactions (e.g. an invoice and its payments).
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "BTreeSet::is_empty")
+    )]
+    pub links: BTreeSet<String>,
+    /// Arbitrary key/value pairs associated with this transaction
+    /// (e.g. a bank reference number).
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl Transaction {
@@ -235,10 +378,73 @@ impl Transaction {
         Transaction {
             description: description.map(|s| s.into()),
             date,
+            time: None,
+            sequence: None,
             elements,
+            payee: None,
+            tags: BTreeSet::new(),
+            links: BTreeSet::new(),
+            metadata: BTreeMap::new(),
         }
     }
 
+    /// Set the time of day that this transaction occurred.
+    pub fn with_time(mut self, time: NaiveTime) -> Transaction {
+        self.time = Some(time);
+        self
+    }
+
+    /// Set the sequence number used to order this transaction
+    /// relative to other actions at the same date and time.
+    pub fn with_sequence(mut self, sequence: u32) -> Transaction {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// Set the `payee` of this transaction.
+    pub fn with_payee<S: Into<String>>(mut self, payee: S) -> Transaction {
+        self.payee = Some(payee.into());
+        self
+    }
+
+    /// Add a tag to this transaction.
+    pub fn with_tag<S: Into<String>>(mut self, tag: S) -> Transaction {
+        self.tags.insert(tag.into());
+        self
+    }
+
+    /// Add a link to this transaction.
+    pub fn with_link<S: Into<String>>(mut self, link: S) -> Transaction {
+        self.links.insert(link.into());
+        self
+    }
+
+    /// Add a key/value pair to the `metadata` of this transaction.
+    pub fn with_metadata<K: Into<String>, V: Into<String>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> Transaction {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns true if this transaction has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    /// Returns true if this transaction has the given link.
+    pub fn has_link(&self, link: &str) -> bool {
+        self.links.contains(link)
+    }
+
+    /// Get the value associated with the given `key` in the
+    /// `metadata` of this transaction.
+    pub fn get_metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(|value| value.as_str())
+    }
+
     /// Create a new simple [Transaction](Transaction), containing
     /// only two elements, transfering an `amount` from `from_account`
     /// to `to_account` on the given `date`, with the given
@@ -303,7 +509,7 @@ impl Transaction {
 
 impl ActionTypeFor<ActionType> for Transaction {
     fn action_type(&self) -> ActionType {
-        todo!()
+        ActionType::Transaction
     }
 }
 
@@ -321,11 +527,31 @@ where
         self.date
     }
 
+    fn time(&self) -> Option<NaiveTime> {
+        self.time
+    }
+
+    fn sequence(&self) -> Option<u32> {
+        self.sequence
+    }
+
+    fn as_transaction(&self) -> Option<&Transaction> {
+        Some(self)
+    }
+
+    fn account_ids(&self) -> Vec<AccountID> {
+        self.elements.iter().map(|e| e.account_id).collect()
+    }
+
+    fn amounts(&self) -> Vec<Commodity> {
+        self.elements.iter().filter_map(|e| e.amount).collect()
+    }
+
     fn perform(&self, program_state: &mut ProgramState<AT, ATV>) -> Result<(), AccountingError> {
         // check that the transaction has at least 2 elements
         if self.elements.len() < 2 {
             return Err(AccountingError::InvalidTransaction(
-                self.clone(),
+                Box::new(self.clone()),
                 String::from("a transaction cannot have less than 2 elements"),
             ));
         }
@@ -341,19 +567,41 @@ where
                     empty_amount_element = Some(i)
                 } else {
                     return Err(AccountingError::InvalidTransaction(
-                        self.clone(),
+                        Box::new(self.clone()),
                         String::from("multiple elements with no amount specified"),
                     ));
                 }
             }
         }
 
+        // check that all the accounts referenced by this transaction
+        // can be modified, before any changes are made.
+        for element in &self.elements {
+            let account_state = program_state
+                .get_account_state(&element.account_id)
+                .ok_or(AccountingError::MissingAccountState(element.account_id))?;
+
+            if let Some(amount) = element.amount {
+                account_state.check_posting(amount)?;
+            }
+        }
+
         let sum_commodity_type_id = match empty_amount_element {
             Some(empty_i) => {
                 let empty_element = self.elements.get(empty_i).unwrap();
 
                 match program_state.get_account(&empty_element.account_id) {
-                    Some(account) => account.commodity_type_id,
+                    Some(account) => {
+                        if account.booking_method.is_some() {
+                            return Err(AccountingError::InvalidTransaction(
+                                Box::new(self.clone()),
+                                String::from(
+                                    "the amount is required for an element in an account which tracks lots",
+                                ),
+                            ));
+                        }
+                        account.commodity_type_id
+                    }
                     None => {
                         return Err(AccountingError::MissingAccountState(
                             empty_element.account_id,
@@ -361,35 +609,230 @@ where
                     }
                 }
             }
-            None => {
-                let account_id = self
-                    .elements
-                    .get(0)
-                    .expect("there should be at least 2 elements in the transaction")
-                    .account_id;
-
-                match program_state.get_account(&account_id) {
-                    Some(account) => account.commodity_type_id,
-                    None => return Err(AccountingError::MissingAccountState(account_id)),
-                }
-            }
+            None => self
+                .elements
+                .first()
+                .expect("there should be at least 2 elements in the transaction")
+                .weight_commodity_type_id()
+                .expect("all elements should have an amount"),
         };
 
         let mut sum = Commodity::new(Decimal::zero(), sum_commodity_type_id);
 
         let mut modified_elements = self.elements.clone();
+        let mut disposals: Vec<Disposal> = Vec::new();
+        let mut realized_gain: Option<Commodity> = None;
+
+        // The lots of each account which tracks lots, as they will be
+        // after this transaction. Every element is booked against
+        // these (rather than the lots before the transaction), and
+        // they are only stored in the account states once the
+        // transaction has been validated.
+        let mut working_lots: HashMap<AccountID, Vec<Lot>> = HashMap::new();
+
+        // Elements which reduce an account which tracks lots without a
+        // price, where the same amount is added to another account
+        // which tracks lots without a cost, are a transfer of the lots
+        // between the accounts (rather than a disposal). Maps the
+        // index of the reducing element to the index of the receiving
+        // element.
+        let tracks_lots = |account_id: &AccountID| {
+            program_state
+                .get_account(account_id)
+                .and_then(|account| account.booking_method)
+                .is_some()
+        };
+        let mut lot_transfers: HashMap<usize, usize> = HashMap::new();
+        for (i, element) in self.elements.iter().enumerate() {
+            let amount = match element.amount {
+                Some(amount) if amount.value.is_sign_negative() && !amount.value.is_zero() => {
+                    amount
+                }
+                _ => continue,
+            };
 
-        // Calculate the sum of elements (not including the empty element if there is one)
+            if element.price.is_some() || !tracks_lots(&element.account_id) {
+                continue;
+            }
+
+            let receiving = self.elements.iter().enumerate().find(|(j, other)| {
+                *j != i
+                    && other.account_id != element.account_id
+                    && other.amount == Some(amount.neg())
+                    && other.cost.is_none()
+                    && other.price.is_none()
+                    && !lot_transfers.values().any(|receiving| receiving == j)
+                    && tracks_lots(&other.account_id)
+            });
+
+            if let Some((j, _)) = receiving {
+                lot_transfers.insert(i, j);
+            }
+        }
+
+        // Calculate the sum of the weights of the elements (not
+        // including the empty element if there is one), booking any
+        // changes to lots.
         for (i, element) in self.elements.iter().enumerate() {
-            if let Some(empty_i) = empty_amount_element {
-                if i != empty_i {
-                    //TODO: perform commodity type conversion here if required
-                    sum = match sum.add(&element.amount.as_ref().unwrap()) {
-                        Ok(value) => value,
-                        Err(error) => return Err(AccountingError::Commodity(error)),
+            let amount = match element.amount {
+                Some(amount) => amount,
+                None => continue,
+            };
+
+            // an element receiving transferred lots is booked along
+            // with the element it receives them from
+            if lot_transfers.values().any(|receiving| *receiving == i) {
+                continue;
+            }
+
+            let account_state = program_state
+                .get_account_state(&element.account_id)
+                .expect("account state should have been checked");
+
+            let weight = match account_state.account.booking_method {
+                Some(method) if amount.value.is_sign_negative() && !amount.value.is_zero() => {
+                    let lots = working_lots
+                        .entry(element.account_id)
+                        .or_insert_with(|| account_state.lots.clone());
+
+                    let reductions =
+                        book_lots(lots, amount.neg(), method, element.lot_label.as_deref())
+                            .map_err(|error| {
+                                AccountingError::InvalidTransaction(
+                                    Box::new(self.clone()),
+                                    error.to_string(),
+                                )
+                            })?;
+
+                    // the parts of the lots which were booked, at the
+                    // cost they were booked at
+                    let booked_lots: Vec<Lot> = reductions
+                        .iter()
+                        .map(|reduction| {
+                            let lot = &lots[reduction.index];
+                            Lot::new(
+                                reduction.quantity,
+                                reduction.cost,
+                                lot.acquisition_date,
+                                lot.label.clone(),
+                            )
+                        })
+                        .collect();
+                    apply_lot_reductions(lots, &reductions, method)?;
+
+                    match lot_transfers.get(&i) {
+                        Some(receiving) => {
+                            // move the booked lots with their original
+                            // cost, the weights of the two elements
+                            // cancel each other out
+                            let receiving_account_id = self.elements[*receiving].account_id;
+                            let receiving_lots = program_state
+                                .get_account_state(&receiving_account_id)
+                                .expect("account state should have been checked")
+                                .lots
+                                .clone();
+                            working_lots
+                                .entry(receiving_account_id)
+                                .or_insert(receiving_lots)
+                                .extend(booked_lots);
+
+                            Commodity::zero(sum_commodity_type_id)
+                        }
+                        None => {
+                            let price = element.price.ok_or_else(|| {
+                                AccountingError::InvalidTransaction(
+                                    Box::new(self.clone()),
+                                    String::from(
+                                        "a price is required for an element which reduces an account which tracks lots, unless the lots are transferred to another account which tracks lots",
+                                    ),
+                                )
+                            })?;
+
+                            let mut cost_basis: Option<Commodity> = None;
+                            for booked_lot in booked_lots {
+                                let lot_cost = booked_lot.cost_basis();
+                                cost_basis = Some(match cost_basis {
+                                    Some(cost_basis) => cost_basis.add(&lot_cost)?,
+                                    None => lot_cost,
+                                });
+
+                                let proceeds = Commodity::new(
+                                    booked_lot.quantity.value * price.value,
+                                    price.type_id,
+                                );
+                                let gain = proceeds.sub(&lot_cost)?;
+                                realized_gain = Some(match realized_gain {
+                                    Some(realized_gain) => realized_gain.add(&gain)?,
+                                    None => gain,
+                                });
+
+                                disposals.push(Disposal {
+                                    account_id: element.account_id,
+                                    quantity: booked_lot.quantity,
+                                    acquisition_date: booked_lot.acquisition_date,
+                                    disposal_date: self.date,
+                                    lot_label: booked_lot.label,
+                                    cost_basis: lot_cost,
+                                    proceeds,
+                                    gain,
+                                });
+                            }
+
+                            cost_basis
+                                .expect("at least one lot should have been booked")
+                                .neg()
+                        }
                     }
                 }
-            }
+                Some(_) => {
+                    let cost = element.cost.or(element.price).ok_or_else(|| {
+                        AccountingError::InvalidTransaction(
+                            Box::new(self.clone()),
+                            String::from(
+                                "a cost is required for an element which adds to an account which tracks lots",
+                            ),
+                        )
+                    })?;
+
+                    working_lots
+                        .entry(element.account_id)
+                        .or_insert_with(|| account_state.lots.clone())
+                        .push(Lot::new(amount, cost, self.date, element.lot_label.clone()));
+                    Commodity::new(amount.value * cost.value, cost.type_id)
+                }
+                None => element.weight(sum_commodity_type_id)?,
+            };
+
+            sum = sum.add(&weight)?;
+        }
+
+        // Post any realized gain (or loss) to the realized gains account
+        if let Some(realized_gain) = realized_gain.filter(|gain| !gain.value.is_zero()) {
+            let realized_gains_account_id =
+                match program_state.realized_gains_account_id {
+                    Some(account_id) => account_id,
+                    None => return Err(AccountingError::InvalidTransaction(
+                        Box::new(self.clone()),
+                        String::from(
+                            "a realized gain was made, but no realized gains account is configured",
+                        ),
+                    )),
+                };
+
+            let gain_posting = realized_gain.neg();
+            program_state
+                .get_account_state(&realized_gains_account_id)
+                .ok_or(AccountingError::MissingAccountState(
+                    realized_gains_account_id,
+                ))?
+                .check_posting(gain_posting)?;
+
+            sum = sum.add(&gain_posting)?;
+            modified_elements.push(TransactionElement::new(
+                realized_gains_account_id,
+                Some(gain_posting),
+                None,
+            ));
         }
 
         // Calculate the value to use for the empty element (negate the sum of the other elements)
@@ -399,21 +842,127 @@ where
             let negated_sum = sum.neg();
             modified_emtpy_element.amount = Some(negated_sum);
 
+            program_state
+                .get_account_state(&modified_emtpy_element.account_id)
+                .ok_or(AccountingError::MissingAccountState(
+                    modified_emtpy_element.account_id,
+                ))?
+                .check_posting(negated_sum)?;
+
             sum = match sum.add(&negated_sum) {
                 Ok(value) => value,
                 Err(error) => return Err(AccountingError::Commodity(error)),
             }
         }
 
+        // Post a residual within the rounding tolerance to the
+        // rounding account
         if sum.value != Decimal::zero() {
-            return Err(AccountingError::InvalidTransaction(
-                self.clone(),
-                String::from("sum of transaction elements does not equal zero"),
-            ));
+            let within_tolerance = program_state
+                .rounding_tolerances
+                .get(&sum.type_id)
+                .map_or(false, |tolerance| sum.value.abs() <= *tolerance);
+
+            match program_state.rounding_account_id {
+                Some(rounding_account_id) if within_tolerance => {
+                    let residual = sum.neg();
+                    program_state
+                        .get_account_state(&rounding_account_id)
+                        .ok_or(AccountingError::MissingAccountState(rounding_account_id))?
+                        .check_posting(residual)?;
+
+                    modified_elements.push(
+                        TransactionElement::new(rounding_account_id, Some(residual), None)
+                            .with_memo("Rounding residual"),
+                    );
+                }
+                _ => {
+                    return Err(AccountingError::InvalidTransaction(
+                        Box::new(self.clone()),
+                        String::from("sum of transaction elements does not equal zero"),
+                    ))
+                }
+            }
+        }
+
+        // check the constraints of the accounts, before any changes
+        // are made
+        let mut balances: HashMap<(AccountID, CommodityTypeID), Commodity> = HashMap::new();
+        for element in &modified_elements {
+            if let Some(amount) = element.amount {
+                let key = (element.account_id, amount.type_id);
+                let account_state = program_state
+                    .get_account_state(&element.account_id)
+                    .ok_or(AccountingError::MissingAccountState(element.account_id))?;
+
+                if !account_state.account.inventory
+                    && account_state.amount.type_id != amount.type_id
+                {
+                    return Err(AccountingError::InvalidTransaction(
+                        Box::new(self.clone()),
+                        format!(
+                            "account {} is not an inventory, so it cannot hold {}",
+                            element.account_id, amount.type_id
+                        ),
+                    ));
+                }
+
+                let balance = match balances.get(&key) {
+                    Some(balance) => *balance,
+                    None => account_state.balance(amount.type_id),
+                };
+                balances.insert(key, balance.add(&amount)?);
+            }
+        }
+
+        // balance constraints are only checked once for each account
+        // and commodity type, while amount constraints are checked for
+        // each element
+        let mut checked_balances: HashSet<(AccountID, CommodityTypeID)> = HashSet::new();
+        for element in &modified_elements {
+            let (amount, balance) = match element.amount.and_then(|amount| {
+                balances
+                    .get(&(element.account_id, amount.type_id))
+                    .map(|balance| (amount, *balance))
+            }) {
+                Some(amount_balance) => amount_balance,
+                None => continue,
+            };
+            let check_balance = checked_balances.insert((element.account_id, amount.type_id));
+
+            let account = program_state
+                .get_account(&element.account_id)
+                .ok_or(AccountingError::MissingAccountState(element.account_id))?;
+
+            let violations: Vec<ConstraintViolation> = account
+                .constraints
+                .iter()
+                .filter(|constraint| {
+                    !constraint.is_satisfied_by_amount(amount)
+                        || (check_balance && !constraint.is_satisfied_by_balance(balance))
+                })
+                .map(|constraint| ConstraintViolation {
+                    account_id: element.account_id,
+                    constraint: constraint.clone(),
+                    transaction: self.clone(),
+                    balance,
+                })
+                .collect();
+
+            match program_state.constraint_mode {
+                ConstraintMode::Error => {
+                    if let Some(violation) = violations.into_iter().next() {
+                        return Err(AccountingError::AccountConstraintViolated(Box::new(
+                            violation,
+                        )));
+                    }
+                }
+                ConstraintMode::Record => program_state.constraint_violations.extend(violations),
+            }
         }
 
         for transaction in &modified_elements {
-            let mut account_state = program_state
+            let account_state = program_state
                 .get_account_state_mut(&transaction.account_id)
                 .unwrap_or_else(||
                     panic!(
@@ -422,21 +971,11 @@ where
                     )
                 );
 
-            match account_state.status {
-                AccountStatus::Closed => Err(AccountingError::InvalidAccountStatus {
-                    account_id: transaction.account_id,
-                    status: account_state.status,
-                }),
-                _ => Ok(()),
-            }?;
-
-            // TODO: perform the commodity type conversion using the exchange rate (if present)
-
             let transaction_amount = match &transaction.amount {
                 Some(amount) => amount,
                 None => {
                     return Err(AccountingError::InvalidTransaction(
-                        self.clone(),
+                        Box::new(self.clone()),
                         String::from(
                             "unable to calculate all required amounts for this transaction",
                         ),
@@ -444,19 +983,45 @@ where
                 }
             };
 
-            account_state.amount = match account_state.amount.add(transaction_amount) {
-                Ok(commodity) => commodity,
-                Err(err) => {
-                    return Err(AccountingError::Commodity(err));
-                }
-            }
+            account_state.post(transaction_amount)?;
+        }
+
+        for (account_id, lots) in working_lots {
+            program_state
+                .get_account_state_mut(&account_id)
+                .expect("account state should have been checked")
+                .lots = lots;
         }
 
+        program_state.disposals.extend(disposals);
+
+        program_state.record_transaction(Transaction {
+            elements: modified_elements,
+            ..self.clone()
+        });
+
         Ok(())
     }
 }
 
 /// An element of a [Transaction](Transaction).
+///
+/// The weight of an element (used to check that the transaction
+/// balances) is its `amount`, unless a `cost` or `price` is
+/// specified, in which case the weight is the `amount` multiplied by
+/// the `cost` or `price`. If the element has an `exchange_rate`, it
+/// is used to convert the `amount` to the commodity type of the
+/// other elements in the transaction.
+///
+/// When an element reduces an account which tracks
+/// [Lot](crate::Lot)s, the weight is the negated cost of the lots
+/// which were reduced, and if a `price` is specified, the gain
+/// realized by the sale is posted to the realized gains account of
+/// the [ProgramState](ProgramState). If no `price` is specified, the
+/// same amount needs to be added (without a `cost`) to another account
+/// which tracks lots by another element, and the reduced lots are
+/// transferred to that account with their original cost and
+/// acquisition date.
 #[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct TransactionElement {
@@ -474,6 +1039,47 @@ pub struct TransactionElement {
     /// The exchange rate to use for converting the amount in this element
     /// to a different [CommodityType](commodity::CommodityType).
     pub exchange_rate: Option<ExchangeRate>,
+
+    /// The cost of each unit of the `amount`, used when acquiring a
+    /// new [Lot](crate::Lot).
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub cost: Option<Commodity>,
+
+    /// The price of each unit of the `amount`, used to calculate the
+    /// proceeds when disposing of [Lot](crate::Lot)s (where it is
+    /// required, unless the lots are transferred to another account).
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub price: Option<Commodity>,
+
+    /// The label of the [Lot](crate::Lot) to create when acquiring
+    /// units, or the label of the lot to reduce when disposing of
+    /// units.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub lot_label: Option<String>,
+
+    /// A memo describing this element.
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub memo: Option<String>,
+
+    /// Arbitrary key/value pairs associated with this element (e.g.
+    /// an invoice id).
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl TransactionElement {
@@ -487,6 +1093,79 @@ impl TransactionElement {
             account_id,
             amount,
             exchange_rate,
+            cost: None,
+            price: None,
+            lot_label: None,
+            memo: None,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Set the `memo` of this element.
+    pub fn with_memo<S: Into<String>>(mut self, memo: S) -> TransactionElement {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    /// Add a key/value pair to the `metadata` of this element.
+    pub fn with_metadata<K: Into<String>, V: Into<String>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> TransactionElement {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Get the value associated with the given `key` in the
+    /// `metadata` of this element.
+    pub fn get_metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(|value| value.as_str())
+    }
+
+    /// Set the `cost` of each unit of the `amount` of this element.
+    pub fn with_cost(mut self, cost: Commodity) -> TransactionElement {
+        self.cost = Some(cost);
+        self
+    }
+
+    /// Set the `price` of each unit of the `amount` of this element.
+    pub fn with_price(mut self, price: Commodity) -> TransactionElement {
+        self.price = Some(price);
+        self
+    }
+
+    /// Set the `lot_label` of this element.
+    pub fn with_lot_label<S: Into<String>>(mut self, lot_label: S) -> TransactionElement {
+        self.lot_label = Some(lot_label.into());
+        self
+    }
+
+    /// The id of the commodity type of this element's weight, if
+    /// it has an amount.
+    fn weight_commodity_type_id(&self) -> Option<CommodityTypeID> {
+        self.cost
+            .or(self.price)
+            .map(|c| c.type_id)
+            .or_else(|| self.amount.map(|a| a.type_id))
+    }
+
+    /// The weight of this element (in an account which does not
+    /// track lots), used to check that a transaction balances.
+    fn weight(&self, sum_commodity_type_id: CommodityTypeID) -> Result<Commodity, AccountingError> {
+        let amount = self
+            .amount
+            .expect("weight should only be calculated for elements with an amount");
+
+        if let Some(rate) = self.cost.or(self.price) {
+            return Ok(Commodity::new(amount.value * rate.value, rate.type_id));
+        }
+
+        match &self.exchange_rate {
+            Some(exchange_rate) if amount.type_id != sum_commodity_type_id => {
+                Ok(exchange_rate.convert(amount, sum_commodity_type_id)?)
+            }
+            _ => Ok(amount),
         }
     }
 }
@@ -494,12 +1173,23 @@ impl TransactionElement {
 /// A type of [Action](Action) to edit the
 /// [AccountStatus](AccountStatus) of a given [Account](crate::Account)'s
 /// [AccountState](super::AccountState).
+///
+/// The change is recorded in the account state's `status_history`.
+/// An account can only be closed when its balance is zero, otherwise
+/// an [AccountingError::NonZeroAccountClose] error is returned,
+/// unless a sweep account is specified using
+/// [with_sweep_account()](EditAccountStatus::with_sweep_account()).
 #[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct EditAccountStatus {
     account_id: AccountID,
     newstatus: AccountStatus,
     date: NaiveDate,
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    sweep_account_id: Option<AccountID>,
 }
 
 impl EditAccountStatus {
@@ -513,8 +1203,16 @@ impl EditAccountStatus {
             account_id,
             newstatus,
             date,
+            sweep_account_id: None,
         }
     }
+
+    /// When closing the account, transfer any remaining balance into
+    /// the account with the id `sweep_account_id` instead of failing.
+    pub fn with_sweep_account(mut self, sweep_account_id: AccountID) -> EditAccountStatus {
+        self.sweep_account_id = Some(sweep_account_id);
+        self
+    }
 }
 
 impl fmt::Display for EditAccountStatus {
@@ -531,11 +1229,79 @@ where
         self.date
     }
 
+    fn account_ids(&self) -> Vec<AccountID> {
+        let mut account_ids = vec![self.account_id];
+        account_ids.extend(self.sweep_account_id);
+        account_ids
+    }
+
     fn perform(&self, program_state: &mut ProgramState<AT, ATV>) -> Result<(), AccountingError> {
-        let mut account_state = program_state
+        let account_state = program_state
             .get_account_state_mut(&self.account_id)
-            .unwrap();
-        account_state.status = self.newstatus;
+            .ok_or(AccountingError::MissingAccountState(self.account_id))?;
+
+        let account = &account_state.account;
+        let consistent = match self.newstatus {
+            AccountStatus::Open
+            | AccountStatus::Frozen
+            | AccountStatus::ReceiveOnly
+            | AccountStatus::SendOnly
+            | AccountStatus::PendingClose => account.is_open_on(self.date),
+            // an account can only be closed once it has been opened,
+            // and on its declared close date
+            AccountStatus::Closed => {
+                account
+                    .open_date
+                    .map_or(true, |open_date| self.date >= open_date)
+                    && account
+                        .close_date
+                        .map_or(true, |close_date| self.date == close_date)
+            }
+        };
+
+        if !consistent {
+            return Err(AccountingError::AccountStatusDateMismatch {
+                account_id: self.account_id,
+                status: self.newstatus,
+                date: self.date,
+            });
+        }
+
+        let remaining_balances: Vec<Commodity> = match self.newstatus {
+            AccountStatus::Closed => account_state
+                .balances()
+                .into_iter()
+                .filter(|balance| !balance.value.is_zero())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        for balance in remaining_balances {
+            match self.sweep_account_id {
+                Some(sweep_account_id) => {
+                    let transaction = Transaction::new_simple(
+                        Some(format!("Balance of {} swept on closing", balance)),
+                        self.date,
+                        self.account_id,
+                        sweep_account_id,
+                        balance,
+                        None,
+                    );
+                    transaction.perform(program_state)?;
+                }
+                None => {
+                    return Err(AccountingError::NonZeroAccountClose {
+                        account_id: self.account_id,
+                        balance,
+                    })
+                }
+            }
+        }
+
+        program_state
+            .get_account_state_mut(&self.account_id)
+            .ok_or(AccountingError::MissingAccountState(self.account_id))?
+            .set_status(self.newstatus, self.date);
         Ok(())
     }
 }
@@ -548,7 +1314,18 @@ impl ActionTypeFor<ActionType> for EditAccountStatus {
 
 /// A type of [Action](Action) to check and assert the balance of a
 /// given [Account](crate::Account) in its [AccountStatus](AccountStatus) at
-/// the beginning of the given date.
+/// the beginning of the given date, or at the given time of day if
+/// one is specified using [with_time()](BalanceAssertion::with_time()).
+///
+/// By default the balance needs to be equal to the
+/// `expected_balance`, within [Commodity::default_epsilon()]. The
+/// balance can instead be asserted to be within a given
+/// [with_tolerance()](BalanceAssertion::with_tolerance()), or to be
+/// [at_least()](BalanceAssertion::at_least()) or
+/// [at_most()](BalanceAssertion::at_most()) an amount. Using
+/// [with_subaccounts()](BalanceAssertion::with_subaccounts()) the
+/// rolled-up balance of the account and all its descendants (according
+/// to their [parent_id](crate::Account::parent_id)) is asserted.
 ///
 /// When running its [perform()](Action::perform()) method, if this
 /// assertion fails, a [FailedBalanceAssertion](FailedBalanceAssertion)
@@ -558,7 +1335,55 @@ impl ActionTypeFor<ActionType> for EditAccountStatus {
 pub struct BalanceAssertion {
     account_id: AccountID,
     date: NaiveDate,
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    time: Option<NaiveTime>,
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    sequence: Option<u32>,
     expected_balance: Commodity,
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "BalanceComparison::is_equal")
+    )]
+    comparison: BalanceComparison,
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    tolerance: Option<Decimal>,
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    include_subaccounts: bool,
+}
+
+/// How the balance of an account is compared to the expected balance
+/// of a [BalanceAssertion](BalanceAssertion).
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum BalanceComparison {
+    /// The balance is equal to the expected balance.
+    #[default]
+    Equal,
+    /// The balance is greater than or equal to the expected balance
+    /// (a minimum).
+    AtLeast,
+    /// The balance is less than or equal to the expected balance (a
+    /// maximum).
+    AtMost,
+}
+
+impl BalanceComparison {
+    /// Returns true if this is [Equal](BalanceComparison::Equal).
+    pub fn is_equal(&self) -> bool {
+        *self == BalanceComparison::Equal
+    }
 }
 
 impl BalanceAssertion {
@@ -572,9 +1397,134 @@ impl BalanceAssertion {
         BalanceAssertion {
             account_id,
             date,
+            time: None,
+            sequence: None,
             expected_balance,
+            comparison: BalanceComparison::Equal,
+            tolerance: None,
+            include_subaccounts: false,
+        }
+    }
+
+    /// Create a new [BalanceAssertion](BalanceAssertion) that the
+    /// balance is greater than or equal to the `minimum_balance` at
+    /// the beginning of the provided `date`.
+    pub fn at_least(
+        account_id: AccountID,
+        date: NaiveDate,
+        minimum_balance: Commodity,
+    ) -> BalanceAssertion {
+        BalanceAssertion {
+            comparison: BalanceComparison::AtLeast,
+            ..BalanceAssertion::new(account_id, date, minimum_balance)
+        }
+    }
+
+    /// Create a new [BalanceAssertion](BalanceAssertion) that the
+    /// balance is less than or equal to the `maximum_balance` at the
+    /// beginning of the provided `date`.
+    pub fn at_most(
+        account_id: AccountID,
+        date: NaiveDate,
+        maximum_balance: Commodity,
+    ) -> BalanceAssertion {
+        BalanceAssertion {
+            comparison: BalanceComparison::AtMost,
+            ..BalanceAssertion::new(account_id, date, maximum_balance)
+        }
+    }
+
+    /// Set the `tolerance` allowed between the balance and the
+    /// expected balance, instead of
+    /// [Commodity::default_epsilon()].
+    pub fn with_tolerance(mut self, tolerance: Decimal) -> BalanceAssertion {
+        self.tolerance = Some(tolerance);
+        self
+    }
+
+    /// Assert the rolled-up balance of the account and all of its
+    /// descendants, instead of only the balance of the account.
+    pub fn with_subaccounts(mut self) -> BalanceAssertion {
+        self.include_subaccounts = true;
+        self
+    }
+
+    /// The tolerance allowed between the balance and the expected
+    /// balance.
+    pub fn tolerance(&self) -> Decimal {
+        self.tolerance.unwrap_or_else(Commodity::default_epsilon)
+    }
+
+    /// Returns true if the `balance` satisfies this assertion.
+    pub fn is_satisfied_by(&self, balance: Commodity) -> bool {
+        if balance.type_id != self.expected_balance.type_id {
+            return false;
+        }
+
+        let tolerance = self.tolerance();
+        match self.comparison {
+            BalanceComparison::Equal => balance.eq_approx(self.expected_balance, tolerance),
+            BalanceComparison::AtLeast => balance.value >= self.expected_balance.value - tolerance,
+            BalanceComparison::AtMost => balance.value <= self.expected_balance.value + tolerance,
         }
     }
+
+    /// The balance which is being asserted in the `program_state` (of
+    /// the commodity type of the `expected_balance`, so that a single
+    /// commodity held in an [inventory](crate::Account::inventory)
+    /// account can be asserted), which is rolled-up over all the
+    /// descendants of the account if the assertion includes
+    /// subaccounts.
+    pub fn balance<AT, ATV>(
+        &self,
+        program_state: &ProgramState<AT, ATV>,
+    ) -> Result<Commodity, AccountingError>
+    where
+        ATV: ActionTypeValueEnum<AT>,
+    {
+        let type_id = self.expected_balance.type_id;
+        let balance = program_state
+            .get_account_state(&self.account_id)
+            .ok_or(AccountingError::MissingAccountState(self.account_id))?
+            .balance(type_id);
+
+        if !self.include_subaccounts {
+            return Ok(balance);
+        }
+
+        let subtree = account_subtree_ids(
+            self.account_id,
+            program_state
+                .account_states
+                .values()
+                .map(|state| state.account.as_ref()),
+        );
+
+        let mut sum = Commodity::zero(balance.type_id);
+        for account_id in subtree {
+            let state = program_state
+                .get_account_state(&account_id)
+                .ok_or(AccountingError::MissingAccountState(account_id))?;
+            sum = sum.add(&state.balance(type_id))?;
+        }
+
+        Ok(sum)
+    }
+
+    /// Assert the balance at the given `time` on the `date`, after
+    /// any actions which occur earlier on that date, and before any
+    /// transactions at the same time.
+    pub fn with_time(mut self, time: NaiveTime) -> BalanceAssertion {
+        self.time = Some(time);
+        self
+    }
+
+    /// Set the sequence number used to order this assertion relative
+    /// to other actions at the same date and time.
+    pub fn with_sequence(mut self, sequence: u32) -> BalanceAssertion {
+        self.sequence = Some(sequence);
+        self
+    }
 }
 
 impl fmt::Display for BalanceAssertion {
@@ -619,29 +1569,36 @@ where
         self.date
     }
 
+    fn time(&self) -> Option<NaiveTime> {
+        self.time
+    }
+
+    fn sequence(&self) -> Option<u32> {
+        self.sequence
+    }
+
+    fn account_ids(&self) -> Vec<AccountID> {
+        vec![self.account_id]
+    }
+
+    fn amounts(&self) -> Vec<Commodity> {
+        vec![self.expected_balance]
+    }
+
     fn perform(&self, program_state: &mut ProgramState<AT, ATV>) -> Result<(), AccountingError> {
-        let failed_assertion = match program_state.get_account_state(&self.account_id) {
-            Some(state) => {
-                if !state
-                    .amount
-                    .eq_approx(self.expected_balance, Commodity::default_epsilon())
-                {
-                    Some(FailedBalanceAssertion::new(self.clone(), state.amount))
-                } else {
-                    None
-                }
-            }
-            None => {
-                return Err(AccountingError::MissingAccountState(self.account_id));
-            }
-        };
+        let balance = self.balance(program_state)?;
 
-        if let Some(failed_assertion) = failed_assertion {
-            program_state.record_failed_balance_assertion(failed_assertion)
+        if !self.is_satisfied_by(balance) {
+            program_state
+                .record_failed_balance_assertion(FailedBalanceAssertion::new(self.clone(), balance))
         }
 
         Ok(())
     }
+
+    fn as_balance_assertion(&self) -> Option<&BalanceAssertion> {
+        Some(self)
+    }
 }
 
 impl ActionTypeFor<ActionType> for BalanceAssertion {
@@ -650,121 +1607,1556 @@ impl ActionTypeFor<ActionType> for BalanceAssertion {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::ActionType;
-    use crate::{
-        Account, AccountStatus, AccountingError, ActionTypeValue, BalanceAssertion, Program,
-        ProgramState, Transaction,
-    };
-    use chrono::NaiveDate;
-    use commodity::{Commodity, CommodityType};
-    use rust_decimal::Decimal;
-    use std::{collections::HashSet, rc::Rc};
-
-    #[test]
-    fn action_type_order() {
-        let mut tested_types: HashSet<ActionType> = HashSet::new();
+/// A type of [Action](Action) to record the price of one unit of a
+/// [CommodityType](commodity::CommodityType) in another commodity
+/// type on a given date.
+///
+/// When running its [perform()](Action::perform()) method, the price
+/// is recorded in the [PriceDatabase](crate::PriceDatabase) of the
+/// [ProgramState](ProgramState).
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Price {
+    commodity_type_id: CommodityTypeID,
+    price: Commodity,
+    date: NaiveDate,
+}
 
-        let mut action_types_unordered: Vec<ActionType> = vec![
-            ActionType::Transaction,
-            ActionType::EditAccountStatus,
-            ActionType::BalanceAssertion,
-            ActionType::EditAccountStatus,
-            ActionType::Transaction,
-            ActionType::BalanceAssertion,
-        ];
+impl Price {
+    /// Create a new [Price](Price), recording that one unit of the
+    /// commodity type with the id `commodity_type_id` is worth
+    /// `price` on the given `date`.
+    pub fn new(commodity_type_id: CommodityTypeID, price: Commodity, date: NaiveDate) -> Price {
+        Price {
+            commodity_type_id,
+            price,
+            date,
+        }
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Price")
+    }
+}
+
+impl<AT, ATV> Action<AT, ATV> for Price
+where
+    ATV: ActionTypeValueEnum<AT>,
+{
+    fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    fn amounts(&self) -> Vec<Commodity> {
+        vec![self.price]
+    }
+
+    fn perform(&self, program_state: &mut ProgramState<AT, ATV>) -> Result<(), AccountingError> {
+        program_state
+            .price_database
+            .insert(self.date, self.commodity_type_id, self.price);
+        Ok(())
+    }
+}
+
+impl ActionTypeFor<ActionType> for Price {
+    fn action_type(&self) -> ActionType {
+        ActionType::Price
+    }
+}
+
+/// A type of [Action](Action) to pad the balance of an
+/// [Account](crate::Account) using funds from a `source_account`
+/// (typically an equity account such as `Equity:Opening-Balances`).
+///
+/// When running its [perform()](Action::perform()) method, the
+/// [Program](crate::Program) is searched for the next
+/// [BalanceAssertion](BalanceAssertion) for the padded account, and a
+/// [Transaction](Transaction) is performed on the date of this
+/// action, so that this assertion will pass. This is useful when
+/// beginning to track an account with an unknown history.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pad {
+    account_id: AccountID,
+    source_account_id: AccountID,
+    date: NaiveDate,
+}
+
+impl Pad {
+    /// Create a new [Pad](Pad).
+    pub fn new(account_id: AccountID, source_account_id: AccountID, date: NaiveDate) -> Pad {
+        Pad {
+            account_id,
+            source_account_id,
+            date,
+        }
+    }
+}
+
+impl fmt::Display for Pad {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pad Account")
+    }
+}
+
+impl<AT, ATV> Action<AT, ATV> for Pad
+where
+    ATV: ActionTypeValueEnum<AT>,
+{
+    fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    fn account_ids(&self) -> Vec<AccountID> {
+        vec![self.account_id, self.source_account_id]
+    }
+
+    fn perform(&self, program_state: &mut ProgramState<AT, ATV>) -> Result<(), AccountingError> {
+        let start_index = program_state.current_action_index() + 1;
+        let (assertion_index, assertion) = program_state
+            .remaining_actions()
+            .iter()
+            .enumerate()
+            .find_map(|(i, action)| {
+                action
+                    .as_action()
+                    .as_balance_assertion()
+                    .filter(|assertion| assertion.account_id == self.account_id)
+                    .map(|assertion| (start_index + i, assertion.clone()))
+            })
+            .ok_or(AccountingError::UnusedPad(self.account_id))?;
+
+        let account_ids = if assertion.include_subaccounts {
+            account_subtree_ids(
+                self.account_id,
+                program_state
+                    .account_states
+                    .values()
+                    .map(|state| state.account.as_ref()),
+            )
+        } else {
+            HashSet::from([self.account_id])
+        };
+
+        // the balance the account will have when the assertion is
+        // performed, if no padding is applied.
+        let unpadded_balance =
+            assertion.balance(&program_state.simulate_until(assertion_index, &account_ids)?)?;
+
+        if assertion.is_satisfied_by(unpadded_balance) {
+            return Ok(());
+        }
+
+        let padding = assertion.expected_balance.sub(&unpadded_balance)?;
+
+        let transaction = Transaction::new_simple(
+            Some(format!(
+                "Padding inserted for balance of {}",
+                assertion.expected_balance
+            )),
+            self.date,
+            self.source_account_id,
+            self.account_id,
+            padding,
+            None,
+        );
+
+        transaction.perform(program_state)
+    }
+}
+
+impl ActionTypeFor<ActionType> for Pad {
+    fn action_type(&self) -> ActionType {
+        ActionType::Pad
+    }
+}
+
+/// A type of [Action](Action) to accrue interest on the daily balance
+/// of an [Account](crate::Account) from the `start_date` (inclusive)
+/// until the `date` of the action (exclusive), posting it to the
+/// account from an `interest_account` (an income account for interest
+/// earned, or an expense account for interest charged).
+///
+/// Interest is calculated on the balance at the end of each day,
+/// using the balances recorded by the
+/// [ProgramState](ProgramState) while the
+/// [Program](crate::Program) is being executed. A positive balance
+/// accrues positive interest, and a negative balance (such as a
+/// liability) accrues negative interest.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccrueInterest {
+    account_id: AccountID,
+    interest_account_id: AccountID,
+    annual_rate: Decimal,
+    start_date: NaiveDate,
+    date: NaiveDate,
+    method: InterestMethod,
+    day_count: DayCount,
+    #[cfg_attr(
+        feature = "serde-support",
+        serde(default, skip_serializing_if = "RoundingPolicy::is_default")
+    )]
+    rounding: RoundingPolicy,
+}
+
+impl AccrueInterest {
+    /// Create a new [AccrueInterest](AccrueInterest), using
+    /// [Simple](InterestMethod::Simple) interest, the
+    /// [Actual365](DayCount::Actual365) day count convention and the
+    /// default [RoundingPolicy](RoundingPolicy).
+    pub fn new(
+        account_id: AccountID,
+        interest_account_id: AccountID,
+        annual_rate: Decimal,
+        start_date: NaiveDate,
+        date: NaiveDate,
+    ) -> AccrueInterest {
+        AccrueInterest {
+            account_id,
+            interest_account_id,
+            annual_rate,
+            start_date,
+            date,
+            method: InterestMethod::Simple,
+            day_count: DayCount::Actual365,
+            rounding: RoundingPolicy::default(),
+        }
+    }
+
+    /// Set the method used to calculate the interest.
+    pub fn with_method(mut self, method: InterestMethod) -> AccrueInterest {
+        self.method = method;
+        self
+    }
+
+    /// Set the day count convention used to calculate the interest.
+    pub fn with_day_count(mut self, day_count: DayCount) -> AccrueInterest {
+        self.day_count = day_count;
+        self
+    }
+
+    /// Set the policy used to round the accrued interest.
+    pub fn with_rounding(mut self, rounding: RoundingPolicy) -> AccrueInterest {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Calculate the interest accrued on the account's balance during
+    /// the period, using the balance history in the `program_state`.
+    pub fn accrued_interest<AT, ATV>(
+        &self,
+        program_state: &ProgramState<AT, ATV>,
+    ) -> Result<Commodity, AccountingError>
+    where
+        ATV: ActionTypeValueEnum<AT>,
+    {
+        let type_id = program_state
+            .get_account(&self.account_id)
+            .map(|account| account.commodity_type_id)
+            .ok_or(AccountingError::MissingAccountState(self.account_id))?;
+        let mut day = self.start_date;
+        let mut accrued = Decimal::zero();
+
+        while day < self.date {
+            let balance = program_state
+                .balance_on(&self.account_id, type_id, day)
+                .ok_or(AccountingError::MissingAccountState(self.account_id))?;
+
+            let next_day = day.succ_opt().unwrap_or(self.date);
+            let fraction = self.day_count.year_fraction(day, next_day);
+
+            let principal = match self.method {
+                InterestMethod::Simple => balance.value,
+                InterestMethod::Compound => balance.value + accrued,
+            };
+            accrued += principal * self.annual_rate * fraction;
+            day = next_day;
+        }
+
+        Ok(Commodity::new(self.rounding.round(accrued), type_id))
+    }
+}
+
+impl fmt::Display for AccrueInterest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Accrue Interest")
+    }
+}
+
+impl<AT, ATV> Action<AT, ATV> for AccrueInterest
+where
+    ATV: ActionTypeValueEnum<AT>,
+{
+    fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    fn account_ids(&self) -> Vec<AccountID> {
+        vec![self.account_id, self.interest_account_id]
+    }
+
+    fn needs_balance_history(&self) -> bool {
+        true
+    }
+
+    fn perform(&self, program_state: &mut ProgramState<AT, ATV>) -> Result<(), AccountingError> {
+        let interest = self.accrued_interest(program_state)?;
+
+        if interest.value.is_zero() {
+            return Ok(());
+        }
+
+        let transaction = Transaction::new_simple(
+            Some(format!(
+                "Interest accrued from {} to {}",
+                self.start_date, self.date
+            )),
+            self.date,
+            self.interest_account_id,
+            self.account_id,
+            interest,
+            None,
+        );
+
+        transaction.perform(program_state)
+    }
+}
+
+impl ActionTypeFor<ActionType> for AccrueInterest {
+    fn action_type(&self) -> ActionType {
+        ActionType::AccrueInterest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActionType;
+    use crate::{
+        Account, AccountStatus, AccountingError, AccrueInterest, ActionTypeValue, BalanceAssertion,
+        BookingMethod, DayCount, EditAccountStatus, InterestMethod, Lot, Pad, PostingDirection,
+        Price, PriceLookup, Program, ProgramState, Transaction, TransactionElement,
+    };
+    use chrono::{NaiveDate, NaiveTime};
+    use commodity::{Commodity, CommodityType, CommodityTypeID};
+    use rust_decimal::Decimal;
+    use std::{collections::HashSet, rc::Rc, str::FromStr};
+
+    #[test]
+    fn action_type_order() {
+        let mut tested_types: HashSet<ActionType> = HashSet::new();
+
+        let mut action_types_unordered: Vec<ActionType> = vec![
+            ActionType::Transaction,
+            ActionType::EditAccountStatus,
+            ActionType::Pad,
+            ActionType::Price,
+            ActionType::BalanceAssertion,
+            ActionType::EditAccountStatus,
+            ActionType::Transaction,
+            ActionType::BalanceAssertion,
+            ActionType::Price,
+            ActionType::Pad,
+            ActionType::AccrueInterest,
+        ];
 
         let num_action_types = ActionType::iterator().count();
 
-        action_types_unordered.iter().for_each(|action_type| {
-            tested_types.insert(action_type.clone());
-        });
+        action_types_unordered.iter().for_each(|action_type| {
+            tested_types.insert(action_type.clone());
+        });
+
+        assert_eq!(num_action_types, tested_types.len());
+
+        action_types_unordered.sort();
+
+        let action_types_ordered: Vec<ActionType> = vec![
+            ActionType::EditAccountStatus,
+            ActionType::EditAccountStatus,
+            ActionType::Price,
+            ActionType::Price,
+            ActionType::BalanceAssertion,
+            ActionType::BalanceAssertion,
+            ActionType::Pad,
+            ActionType::Pad,
+            ActionType::Transaction,
+            ActionType::Transaction,
+            ActionType::AccrueInterest,
+        ];
+
+        assert_eq!(action_types_ordered, action_types_unordered);
+    }
+
+    #[test]
+    fn balance_assertion() {
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let account1 = Rc::from(Account::new_with_id(Some("Account 1"), aud.id, None));
+        let account2 = Rc::from(Account::new_with_id(Some("Account 2"), aud.id, None));
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 1, 2).unwrap();
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(
+                Transaction::new_simple::<String>(
+                    None,
+                    date_1,
+                    account1.id,
+                    account2.id,
+                    Commodity::new(Decimal::new(100, 2), &*aud),
+                    None,
+                )
+                .into(),
+            ),
+            // This assertion is expected to fail because it occurs at the start
+            // of the day (before the transaction).
+            Rc::new(
+                BalanceAssertion::new(
+                    account2.id,
+                    date_1,
+                    Commodity::new(Decimal::new(100, 2), &*aud),
+                )
+                .into(),
+            ),
+            // This assertion is expected to pass because it occurs at the end
+            // of the day (after the transaction).
+            Rc::new(
+                BalanceAssertion::new(
+                    account2.id,
+                    date_2,
+                    Commodity::new(Decimal::new(100, 2), &*aud),
+                )
+                .into(),
+            ),
+        ];
+
+        let program = Program::new(actions);
+
+        let accounts = vec![account1, account2];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        match program_state.execute_program(&program) {
+            Err(AccountingError::BalanceAssertionFailed(failure)) => {
+                assert_eq!(
+                    Commodity::new(Decimal::new(0, 2), &*aud),
+                    failure.actual_balance
+                );
+                assert_eq!(date_1, failure.assertion.date);
+            }
+            _ => panic!("Expected an AccountingError:BalanceAssertionFailed"),
+        }
+
+        assert_eq!(1, program_state.failed_balance_assertions.len());
+    }
+
+    #[test]
+    fn balance_assertion_comparisons() {
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let income = Rc::from(Account::new_with_id(Some("Income"), aud.id, None));
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let checking =
+            Rc::from(Account::new_with_id(Some("Checking"), aud.id, None).with_parent_id(bank.id));
+        let savings =
+            Rc::from(Account::new_with_id(Some("Savings"), aud.id, None).with_parent_id(bank.id));
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 1, 2).unwrap();
+        let amount = |amount: &str| Commodity::from_str(amount).unwrap();
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(
+                Transaction::new_simple::<String>(
+                    None,
+                    date_1,
+                    checking.id,
+                    savings.id,
+                    amount("100.005 AUD"),
+                    None,
+                )
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new_simple::<String>(
+                    None,
+                    date_1,
+                    income.id,
+                    bank.id,
+                    amount("20 AUD"),
+                    None,
+                )
+                .into(),
+            ),
+            Rc::new(
+                BalanceAssertion::new(savings.id, date_2, amount("100.00 AUD"))
+                    .with_tolerance(Decimal::new(1, 2))
+                    .into(),
+            ),
+            Rc::new(BalanceAssertion::at_most(savings.id, date_2, amount("150 AUD")).into()),
+            Rc::new(
+                BalanceAssertion::new(bank.id, date_2, amount("20 AUD"))
+                    .with_subaccounts()
+                    .into(),
+            ),
+            // expected to fail, the checking account is overdrawn
+            Rc::new(BalanceAssertion::at_least(checking.id, date_2, amount("0 AUD")).into()),
+        ];
+
+        let program = Program::new(actions);
+        let accounts = vec![income, bank, checking.clone(), savings];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+
+        match program_state.execute_program(&program) {
+            Err(AccountingError::BalanceAssertionFailed(failure)) => {
+                assert_eq!(checking.id, failure.assertion.account_id);
+                assert_eq!(amount("-100.005 AUD"), failure.actual_balance);
+            }
+            _ => panic!("Expected an AccountingError:BalanceAssertionFailed"),
+        }
+
+        assert_eq!(1, program_state.failed_balance_assertions.len());
+    }
+
+    #[test]
+    fn intraday_order() {
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let account1 = Rc::from(Account::new_with_id(Some("Account 1"), aud.id, None));
+        let account2 = Rc::from(Account::new_with_id(Some("Account 2"), aud.id, None));
+
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let time = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let transaction = |description: &str, amount: &str| {
+            Transaction::new_simple(
+                Some(description),
+                date,
+                account1.id,
+                account2.id,
+                Commodity::from_str(amount).unwrap(),
+                None,
+            )
+        };
+        let assertion = |amount: &str| {
+            BalanceAssertion::new(account2.id, date, Commodity::from_str(amount).unwrap())
+        };
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(transaction("Afternoon", "5 AUD").with_time(time(15)).into()),
+            Rc::new(assertion("10 AUD").with_time(time(12)).into()),
+            Rc::new(transaction("Second", "1 AUD").with_sequence(2).into()),
+            Rc::new(transaction("Morning", "9 AUD").with_time(time(9)).into()),
+            Rc::new(transaction("First", "0 AUD").with_sequence(1).into()),
+            // before the transaction at the same time
+            Rc::new(assertion("10 AUD").with_time(time(15)).into()),
+            // at the start of the day
+            Rc::new(assertion("0 AUD").into()),
+        ];
+
+        let program = Program::new(actions);
+        let descriptions: Vec<Option<&str>> = program
+            .actions
+            .iter()
+            .map(|action| match action.as_ref() {
+                ActionTypeValue::Transaction(transaction) => transaction.description.as_deref(),
+                ActionTypeValue::BalanceAssertion(_) => Some("Assertion"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                Some("Assertion"),
+                Some("First"),
+                Some("Second"),
+                Some("Morning"),
+                Some("Assertion"),
+                Some("Assertion"),
+                Some("Afternoon"),
+            ],
+            descriptions
+        );
+
+        let accounts = vec![account1, account2];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.execute_program(&program).unwrap();
+    }
+
+    #[test]
+    fn pad() {
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let expenses = Rc::from(Account::new_with_id(Some("Expenses"), aud.id, None));
+        let opening_balances =
+            Rc::from(Account::new_with_id(Some("Opening Balances"), aud.id, None));
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 1, 2).unwrap();
+        let date_3 = NaiveDate::from_ymd_opt(2020, 1, 3).unwrap();
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(Pad::new(bank.id, opening_balances.id, date_1).into()),
+            Rc::new(
+                Transaction::new_simple::<String>(
+                    None,
+                    date_2,
+                    bank.id,
+                    expenses.id,
+                    Commodity::new(Decimal::new(2000, 2), &*aud),
+                    None,
+                )
+                .into(),
+            ),
+            Rc::new(
+                BalanceAssertion::new(
+                    bank.id,
+                    date_3,
+                    Commodity::new(Decimal::new(10000, 2), &*aud),
+                )
+                .into(),
+            ),
+        ];
+
+        let program = Program::new(actions);
+
+        let accounts = vec![bank.clone(), expenses, opening_balances.clone()];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.execute_program(&program).unwrap();
+
+        assert_eq!(
+            Commodity::new(Decimal::new(-12000, 2), &*aud),
+            program_state
+                .get_account_state(&opening_balances.id)
+                .unwrap()
+                .amount
+        );
+
+        let padding = program_state.journal.first().unwrap();
+        assert_eq!(date_1, padding.date);
+        assert_eq!(
+            Some(Commodity::new(Decimal::new(12000, 2), &*aud)),
+            padding.get_element(&bank.id).unwrap().amount
+        );
+    }
+
+    #[test]
+    fn pad_indirect() {
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let savings = Rc::from(Account::new_with_id(Some("Savings"), aud.id, None));
+        let income = Rc::from(Account::new_with_id(Some("Income"), aud.id, None));
+        let opening_balances =
+            Rc::from(Account::new_with_id(Some("Opening Balances"), aud.id, None));
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 1, 2).unwrap();
+        let date_3 = NaiveDate::from_ymd_opt(2020, 1, 3).unwrap();
+        let date_4 = NaiveDate::from_ymd_opt(2020, 1, 4).unwrap();
+
+        let deposit = |account: &Account, amount: i64| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new_simple::<String>(
+                    None,
+                    date_2,
+                    income.id,
+                    account.id,
+                    Commodity::new(Decimal::new(amount, 0), &*aud),
+                    None,
+                )
+                .into(),
+            )
+        };
+
+        // the deposit into the savings account does not refer to the
+        // bank account, but it affects the balance swept into the
+        // bank account when the savings account is closed.
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(Pad::new(bank.id, opening_balances.id, date_1).into()),
+            deposit(&savings, 100),
+            deposit(&opening_balances, 50),
+            Rc::new(
+                EditAccountStatus::new(savings.id, AccountStatus::Closed, date_3)
+                    .with_sweep_account(bank.id)
+                    .into(),
+            ),
+            Rc::new(
+                BalanceAssertion::new(bank.id, date_4, Commodity::new(Decimal::new(300, 0), &*aud))
+                    .into(),
+            ),
+        ];
+
+        let accounts = vec![bank.clone(), savings, income, opening_balances.clone()];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state
+            .execute_program(&Program::new(actions))
+            .unwrap();
+
+        assert!(program_state.failed_balance_assertions.is_empty());
+        assert_eq!(
+            Commodity::new(Decimal::new(-150, 0), &*aud),
+            program_state
+                .get_account_state(&opening_balances.id)
+                .unwrap()
+                .amount
+        );
+        // the padding, the deposits and the sweep, the simulated
+        // transactions are not recorded in the journal
+        assert_eq!(4, program_state.journal.len());
+    }
+
+    #[test]
+    fn accrue_interest() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), usd, None));
+        let equity = Rc::from(Account::new_with_id(Some("Equity"), usd, None));
+        let interest = Rc::from(Account::new_with_id(Some("Interest"), usd, None));
+
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let deposit = |date| {
+            Transaction::new_simple::<String>(
+                None,
+                date,
+                equity.id,
+                bank.id,
+                Commodity::from_str("1000 USD").unwrap(),
+                None,
+            )
+        };
+        let rate = Decimal::from_str("0.0365").unwrap();
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(deposit(date(2020, 1, 1)).into()),
+            Rc::new(
+                AccrueInterest::new(
+                    bank.id,
+                    interest.id,
+                    rate,
+                    date(2020, 1, 1),
+                    date(2020, 2, 1),
+                )
+                .into(),
+            ),
+            Rc::new(deposit(date(2020, 1, 16)).into()),
+            Rc::new(deposit(date(2020, 2, 1)).into()),
+            Rc::new(
+                AccrueInterest::new(
+                    bank.id,
+                    interest.id,
+                    rate,
+                    date(2020, 2, 1),
+                    date(2020, 3, 1),
+                )
+                .with_method(InterestMethod::Compound)
+                .into(),
+            ),
+            Rc::new(
+                AccrueInterest::new(
+                    bank.id,
+                    interest.id,
+                    rate,
+                    date(2020, 1, 1),
+                    date(2020, 3, 1),
+                )
+                .with_day_count(DayCount::Thirty360)
+                .into(),
+            ),
+        ];
+
+        let program = Program::new(actions);
+        let accounts = vec![bank.clone(), equity, interest.clone()];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.execute_program(&program).unwrap();
+
+        // 15 days at 1000 USD, and 16 days at 2000 USD
+        assert_eq!(
+            Commodity::from_str("3004.70 USD").unwrap(),
+            program_state
+                .balance_on(&bank.id, usd, date(2020, 2, 1))
+                .unwrap()
+        );
+        assert_eq!(
+            Commodity::from_str("3004.70 USD").unwrap(),
+            program_state
+                .balance_on(&bank.id, usd, date(2020, 2, 29))
+                .unwrap()
+        );
+        // 8.73 USD for 29 days at 3004.70 USD compounded daily, and
+        // 13.70 USD for the 30/360 accrual
+        assert_eq!(
+            Commodity::from_str("-27.13 USD").unwrap(),
+            program_state
+                .get_account_state(&interest.id)
+                .unwrap()
+                .amount
+        );
+    }
+
+    #[test]
+    fn price() {
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 1, 2).unwrap();
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(Price::new(aapl, Commodity::new(Decimal::new(300, 0), usd), date_2).into()),
+            Rc::new(Price::new(aapl, Commodity::new(Decimal::new(290, 0), usd), date_1).into()),
+        ];
+
+        let program = Program::new(actions);
+        let mut program_state = ProgramState::new(&[], AccountStatus::Open);
+        program_state.execute_program(&program).unwrap();
+
+        assert_eq!(
+            Some(Commodity::new(Decimal::new(290, 0), usd)),
+            program_state
+                .price_database
+                .get_price(&aapl, &usd, date_1, PriceLookup::LatestBefore)
+        );
+        assert_eq!(
+            Some(Commodity::new(Decimal::new(300, 0), usd)),
+            program_state
+                .price_database
+                .get_price(&aapl, &usd, date_2, PriceLookup::LatestBefore)
+        );
+    }
+
+    #[test]
+    fn lots_realized_gains() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let cash = Rc::from(Account::new_with_id(Some("Cash"), usd, None));
+        let gains = Rc::from(Account::new_with_id(Some("Realized Gains"), usd, None));
+        let brokerage = Rc::from(
+            Account::new_with_id(Some("Brokerage"), aapl, None)
+                .with_booking_method(BookingMethod::Fifo),
+        );
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
+        let date_3 = NaiveDate::from_ymd_opt(2020, 3, 1).unwrap();
+
+        let buy = |date: NaiveDate, cost: &str| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new(
+                    Some("Buy"),
+                    date,
+                    vec![
+                        TransactionElement::new(
+                            brokerage.id,
+                            Some(Commodity::from_str("10 AAPL").unwrap()),
+                            None,
+                        )
+                        .with_cost(Commodity::from_str(cost).unwrap()),
+                        TransactionElement::new(cash.id, None, None),
+                    ],
+                )
+                .into(),
+            )
+        };
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            buy(date_1, "100 USD"),
+            buy(date_2, "200 USD"),
+            Rc::new(
+                Transaction::new(
+                    Some("Sell"),
+                    date_3,
+                    vec![
+                        TransactionElement::new(
+                            brokerage.id,
+                            Some(Commodity::from_str("-15 AAPL").unwrap()),
+                            None,
+                        )
+                        .with_price(Commodity::from_str("300 USD").unwrap()),
+                        TransactionElement::new(cash.id, None, None),
+                    ],
+                )
+                .into(),
+            ),
+        ];
+
+        let program = Program::new(actions);
+        let accounts = vec![cash.clone(), gains.clone(), brokerage.clone()];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+
+        // a realized gains account is required to sell at a gain
+        match program_state.clone().execute_program(&program) {
+            Err(AccountingError::InvalidTransaction(_, _)) => {}
+            _ => panic!("Expected an AccountingError::InvalidTransaction"),
+        }
+
+        // a price is required to sell from an account which tracks lots
+        let unpriced_sale: Rc<ActionTypeValue> = Rc::new(
+            Transaction::new(
+                Some("Sell"),
+                date_3,
+                vec![
+                    TransactionElement::new(
+                        brokerage.id,
+                        Some(Commodity::from_str("-5 AAPL").unwrap()),
+                        None,
+                    ),
+                    TransactionElement::new(cash.id, None, None),
+                ],
+            )
+            .into(),
+        );
+        let mut unpriced_state = program_state.clone();
+        unpriced_state.realized_gains_account_id = Some(gains.id);
+        match unpriced_state
+            .execute_program(&Program::new(vec![buy(date_1, "100 USD"), unpriced_sale]))
+        {
+            Err(AccountingError::InvalidTransaction(_, _)) => {}
+            _ => panic!("Expected an AccountingError::InvalidTransaction"),
+        }
+        assert!(unpriced_state.disposals.is_empty());
+
+        // the realized gains account needs to accept the gain
+        let mut frozen_state = program_state.clone();
+        frozen_state.realized_gains_account_id = Some(gains.id);
+        frozen_state
+            .get_account_state_mut(&gains.id)
+            .unwrap()
+            .set_status(AccountStatus::Frozen, date_1);
+        match frozen_state.execute_program(&program) {
+            Err(AccountingError::InvalidAccountStatus {
+                account_id,
+                direction,
+                ..
+            }) => {
+                assert_eq!(gains.id, account_id);
+                assert_eq!(PostingDirection::Outgoing, direction);
+            }
+            _ => panic!("Expected an AccountingError::InvalidAccountStatus"),
+        }
+
+        program_state.realized_gains_account_id = Some(gains.id);
+        program_state.execute_program(&program).unwrap();
+
+        let amount =
+            |account: &Account| program_state.get_account_state(&account.id).unwrap().amount;
+        assert_eq!(Commodity::from_str("1500 USD").unwrap(), amount(&cash));
+        assert_eq!(Commodity::from_str("-2500 USD").unwrap(), amount(&gains));
+        assert_eq!(Commodity::from_str("5 AAPL").unwrap(), amount(&brokerage));
+
+        let lots = &program_state.get_account_state(&brokerage.id).unwrap().lots;
+        assert_eq!(1, lots.len());
+        assert_eq!(Commodity::from_str("5 AAPL").unwrap(), lots[0].quantity);
+        assert_eq!(Commodity::from_str("200 USD").unwrap(), lots[0].cost);
+        assert_eq!(date_2, lots[0].acquisition_date);
+
+        assert_eq!(2, program_state.disposals.len());
+        assert_eq!(
+            Commodity::from_str("2000 USD").unwrap(),
+            program_state.disposals[0].gain
+        );
+        assert_eq!(
+            Commodity::from_str("500 USD").unwrap(),
+            program_state.disposals[1].gain
+        );
+    }
+
+    #[test]
+    fn lots_multiple_reductions() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let cash = Rc::from(Account::new_with_id(Some("Cash"), usd, None));
+        let gains = Rc::from(Account::new_with_id(Some("Realized Gains"), usd, None));
+        let brokerage = Rc::from(
+            Account::new_with_id(Some("Brokerage"), aapl, None)
+                .with_booking_method(BookingMethod::Fifo),
+        );
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
+        let date_3 = NaiveDate::from_ymd_opt(2020, 3, 1).unwrap();
+
+        let buy = |date: NaiveDate, cost: &str| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new(
+                    Some("Buy"),
+                    date,
+                    vec![
+                        TransactionElement::new(
+                            brokerage.id,
+                            Some(Commodity::from_str("10 AAPL").unwrap()),
+                            None,
+                        )
+                        .with_cost(Commodity::from_str(cost).unwrap()),
+                        TransactionElement::new(cash.id, None, None),
+                    ],
+                )
+                .into(),
+            )
+        };
+        let sell = |date: NaiveDate, quantities: &[&str]| -> Rc<ActionTypeValue> {
+            let mut elements: Vec<TransactionElement> = quantities
+                .iter()
+                .map(|quantity| {
+                    TransactionElement::new(
+                        brokerage.id,
+                        Some(Commodity::from_str(quantity).unwrap()),
+                        None,
+                    )
+                    .with_price(Commodity::from_str("300 USD").unwrap())
+                })
+                .collect();
+            elements.push(TransactionElement::new(cash.id, None, None));
+            Rc::new(Transaction::new(Some("Sell"), date, elements).into())
+        };
+
+        // both reductions are booked against the same lots, so the
+        // second continues from where the first finished
+        let program = Program::new(vec![
+            buy(date_1, "100 USD"),
+            buy(date_2, "200 USD"),
+            sell(date_3, &["-5 AAPL", "-10 AAPL"]),
+        ]);
+        let accounts = vec![cash.clone(), gains.clone(), brokerage.clone()];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.realized_gains_account_id = Some(gains.id);
+        program_state.execute_program(&program).unwrap();
+
+        let amount = |state: &ProgramState, account: &Account| {
+            state.get_account_state(&account.id).unwrap().amount
+        };
+        assert_eq!(
+            Commodity::from_str("1500 USD").unwrap(),
+            amount(&program_state, &cash)
+        );
+        assert_eq!(
+            Commodity::from_str("-2500 USD").unwrap(),
+            amount(&program_state, &gains)
+        );
+        assert_eq!(
+            Commodity::from_str("5 AAPL").unwrap(),
+            amount(&program_state, &brokerage)
+        );
+
+        let lots = &program_state.get_account_state(&brokerage.id).unwrap().lots;
+        assert_eq!(1, lots.len());
+        assert_eq!(Commodity::from_str("5 AAPL").unwrap(), lots[0].quantity);
+        assert_eq!(date_2, lots[0].acquisition_date);
+
+        let disposed: Vec<(Commodity, NaiveDate)> = program_state
+            .disposals
+            .iter()
+            .map(|disposal| (disposal.quantity, disposal.acquisition_date))
+            .collect();
+        assert_eq!(
+            vec![
+                (Commodity::from_str("5 AAPL").unwrap(), date_1),
+                (Commodity::from_str("5 AAPL").unwrap(), date_1),
+                (Commodity::from_str("5 AAPL").unwrap(), date_2),
+            ],
+            disposed
+        );
+
+        // the reductions together exceed the lots held, so nothing is
+        // modified
+        let mut failed_state = program_state.clone();
+        match failed_state
+            .execute_program(&Program::new(vec![sell(date_3, &["-3 AAPL", "-3 AAPL"])]))
+        {
+            Err(AccountingError::InvalidTransaction(_, _)) => {}
+            _ => panic!("Expected an AccountingError::InvalidTransaction"),
+        }
+        assert_eq!(
+            Commodity::from_str("5 AAPL").unwrap(),
+            amount(&failed_state, &brokerage)
+        );
+        assert_eq!(
+            Commodity::from_str("1500 USD").unwrap(),
+            amount(&failed_state, &cash)
+        );
+        assert_eq!(
+            program_state.get_account_state(&brokerage.id).unwrap().lots,
+            failed_state.get_account_state(&brokerage.id).unwrap().lots
+        );
+        assert_eq!(3, failed_state.disposals.len());
+    }
+
+    #[test]
+    fn lots_transfer() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let cash = Rc::from(Account::new_with_id(Some("Cash"), usd, None));
+        let brokerage_1 = Rc::from(
+            Account::new_with_id(Some("Brokerage 1"), aapl, None)
+                .with_booking_method(BookingMethod::Fifo),
+        );
+        let brokerage_2 = Rc::from(
+            Account::new_with_id(Some("Brokerage 2"), aapl, None)
+                .with_booking_method(BookingMethod::Fifo),
+        );
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
 
-        assert_eq!(num_action_types, tested_types.len());
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(
+                Transaction::new(
+                    Some("Buy"),
+                    date_1,
+                    vec![
+                        TransactionElement::new(
+                            brokerage_1.id,
+                            Some(Commodity::from_str("10 AAPL").unwrap()),
+                            None,
+                        )
+                        .with_cost(Commodity::from_str("100 USD").unwrap()),
+                        TransactionElement::new(cash.id, None, None),
+                    ],
+                )
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new(
+                    Some("Transfer"),
+                    date_2,
+                    vec![
+                        TransactionElement::new(
+                            brokerage_1.id,
+                            Some(Commodity::from_str("-4 AAPL").unwrap()),
+                            None,
+                        ),
+                        TransactionElement::new(
+                            brokerage_2.id,
+                            Some(Commodity::from_str("4 AAPL").unwrap()),
+                            None,
+                        ),
+                    ],
+                )
+                .into(),
+            ),
+        ];
 
-        action_types_unordered.sort();
+        // no realized gains account is required, because nothing is
+        // disposed of
+        let program = Program::new(actions);
+        let accounts = vec![cash, brokerage_1.clone(), brokerage_2.clone()];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.execute_program(&program).unwrap();
 
-        let action_types_ordered: Vec<ActionType> = vec![
-            ActionType::EditAccountStatus,
-            ActionType::EditAccountStatus,
-            ActionType::BalanceAssertion,
-            ActionType::BalanceAssertion,
-            ActionType::Transaction,
-            ActionType::Transaction,
+        assert!(program_state.disposals.is_empty());
+
+        let lots = |account: &Account| {
+            program_state
+                .get_account_state(&account.id)
+                .unwrap()
+                .lots
+                .clone()
+        };
+        assert_eq!(
+            vec![Lot::new(
+                Commodity::from_str("6 AAPL").unwrap(),
+                Commodity::from_str("100 USD").unwrap(),
+                date_1,
+                None
+            )],
+            lots(&brokerage_1)
+        );
+        assert_eq!(
+            vec![Lot::new(
+                Commodity::from_str("4 AAPL").unwrap(),
+                Commodity::from_str("100 USD").unwrap(),
+                date_1,
+                None
+            )],
+            lots(&brokerage_2)
+        );
+    }
+
+    #[test]
+    fn unused_pad() {
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let opening_balances =
+            Rc::from(Account::new_with_id(Some("Opening Balances"), aud.id, None));
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![Rc::new(
+            Pad::new(
+                bank.id,
+                opening_balances.id,
+                NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            )
+            .into(),
+        )];
+
+        let program = Program::new(actions);
+
+        let accounts = vec![bank.clone(), opening_balances];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        match program_state.execute_program(&program) {
+            Err(AccountingError::UnusedPad(account_id)) => assert_eq!(bank.id, account_id),
+            _ => panic!("Expected an AccountingError::UnusedPad"),
+        }
+    }
+
+    #[test]
+    fn edit_account_status_dates() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let bank = Rc::from(
+            Account::new_with_id(Some("Bank"), aud.id, None)
+                .with_open_date(date(2020, 1, 1))
+                .with_close_date(date(2020, 6, 30)),
+        );
+        let accounts = vec![bank.clone()];
+
+        let execute = |status, date| {
+            let actions: Vec<Rc<ActionTypeValue>> = vec![Rc::new(
+                EditAccountStatus::new(bank.id, status, date).into(),
+            )];
+            let mut program_state = ProgramState::new(&accounts, AccountStatus::Closed);
+            program_state.execute_program(&Program::new(actions))
+        };
+
+        assert!(execute(AccountStatus::Open, date(2020, 1, 1)).is_ok());
+        assert!(execute(AccountStatus::Closed, date(2020, 6, 30)).is_ok());
+
+        for (status, date) in [
+            (AccountStatus::Open, date(2019, 12, 31)),
+            (AccountStatus::Open, date(2020, 6, 30)),
+            (AccountStatus::Closed, date(2020, 3, 1)),
+        ] {
+            match execute(status, date) {
+                Err(AccountingError::AccountStatusDateMismatch {
+                    account_id,
+                    status: error_status,
+                    date: error_date,
+                }) => {
+                    assert_eq!(bank.id, account_id);
+                    assert_eq!(status, error_status);
+                    assert_eq!(date, error_date);
+                }
+                _ => panic!("Expected an AccountingError::AccountStatusDateMismatch"),
+            }
+        }
+    }
+
+    #[test]
+    fn close_account() {
+        let date = |d| NaiveDate::from_ymd_opt(2020, 1, d).unwrap();
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let savings = Rc::from(Account::new_with_id(Some("Savings"), aud.id, None));
+        let accounts = vec![bank.clone(), savings.clone()];
+
+        let deposit: Rc<ActionTypeValue> = Rc::new(
+            Transaction::new_simple::<String>(
+                None,
+                date(1),
+                savings.id,
+                bank.id,
+                Commodity::from_str("10 AUD").unwrap(),
+                None,
+            )
+            .into(),
+        );
+
+        let actions = vec![
+            deposit.clone(),
+            Rc::new(EditAccountStatus::new(bank.id, AccountStatus::Closed, date(2)).into()),
         ];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        match program_state.execute_program(&Program::new(actions)) {
+            Err(AccountingError::NonZeroAccountClose {
+                account_id,
+                balance,
+            }) => {
+                assert_eq!(bank.id, account_id);
+                assert_eq!(Commodity::from_str("10 AUD").unwrap(), balance);
+            }
+            _ => panic!("Expected an AccountingError::NonZeroAccountClose"),
+        }
 
-        assert_eq!(action_types_ordered, action_types_unordered);
+        let actions = vec![
+            deposit.clone(),
+            Rc::new(
+                EditAccountStatus::new(bank.id, AccountStatus::Closed, date(2))
+                    .with_sweep_account(savings.id)
+                    .into(),
+            ),
+            Rc::new(EditAccountStatus::new(bank.id, AccountStatus::Open, date(3)).into()),
+            Rc::new(EditAccountStatus::new(bank.id, AccountStatus::Closed, date(4)).into()),
+        ];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state
+            .execute_program(&Program::new(actions))
+            .unwrap();
+
+        let bank_state = program_state.get_account_state(&bank.id).unwrap();
+        assert_eq!(Commodity::from_str("0 AUD").unwrap(), bank_state.amount);
+        assert_eq!(
+            Commodity::from_str("0 AUD").unwrap(),
+            program_state.get_account_state(&savings.id).unwrap().amount
+        );
+        assert_eq!(3, bank_state.status_history.len());
+        assert_eq!(Some(date(3)), bank_state.opened_on());
+        assert_eq!(Some(date(4)), bank_state.closed_on());
+        // the sweep was recorded in the journal
+        assert_eq!(2, program_state.journal.len());
     }
 
     #[test]
-    fn balance_assertion() {
+    fn inventory_account() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
         let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
-        let account1 = Rc::from(Account::new_with_id(Some("Account 1"), aud.id, None));
-        let account2 = Rc::from(Account::new_with_id(Some("Account 2"), aud.id, None));
+        let broker = Rc::from(Account::new_with_id(Some("Broker"), aud.id, None).with_inventory());
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let accounts = vec![broker.clone(), bank.clone()];
+
+        let buy = |shares: &str, cost: &str| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new::<String>(
+                    None,
+                    date,
+                    vec![
+                        TransactionElement::new(
+                            broker.id,
+                            Some(Commodity::from_str(shares).unwrap()),
+                            None,
+                        )
+                        .with_cost(Commodity::from_str(cost).unwrap()),
+                        TransactionElement::new(bank.id, None, None),
+                    ],
+                )
+                .into(),
+            )
+        };
 
-        let date_1 = NaiveDate::from_ymd(2020, 01, 01);
-        let date_2 = NaiveDate::from_ymd(2020, 01, 02);
         let actions: Vec<Rc<ActionTypeValue>> = vec![
             Rc::new(
                 Transaction::new_simple::<String>(
                     None,
-                    date_1.clone(),
-                    account1.id,
-                    account2.id,
-                    Commodity::new(Decimal::new(100, 2), &*aud),
+                    date,
+                    bank.id,
+                    broker.id,
+                    Commodity::from_str("100 AUD").unwrap(),
                     None,
                 )
                 .into(),
             ),
-            // This assertion is expected to fail because it occurs at the start
-            // of the day (before the transaction).
+            buy("10 AAPL", "150 AUD"),
+            buy("5 MSFT", "200 AUD"),
             Rc::new(
                 BalanceAssertion::new(
-                    account2.id,
-                    date_1.clone(),
-                    Commodity::new(Decimal::new(100, 2), &*aud),
+                    broker.id,
+                    NaiveDate::from_ymd_opt(2020, 1, 2).unwrap(),
+                    Commodity::from_str("10 AAPL").unwrap(),
                 )
                 .into(),
             ),
-            // This assertion is expected to pass because it occurs at the end
-            // of the day (after the transaction).
+        ];
+
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state
+            .execute_program(&Program::new(actions))
+            .unwrap();
+
+        let broker_state = program_state.get_account_state(&broker.id).unwrap();
+        assert_eq!(
+            vec![
+                Commodity::from_str("100 AUD").unwrap(),
+                Commodity::from_str("10 AAPL").unwrap(),
+                Commodity::from_str("5 MSFT").unwrap(),
+            ],
+            broker_state.balances()
+        );
+        assert_eq!(
+            Commodity::from_str("0 GOOG").unwrap(),
+            broker_state.balance(CommodityTypeID::from_str("GOOG").unwrap())
+        );
+        assert_eq!(
+            Commodity::from_str("-2600 AUD").unwrap(),
+            program_state.get_account_state(&bank.id).unwrap().amount
+        );
+
+        // an account which is not an inventory can only hold its own
+        // commodity type
+        let actions: Vec<Rc<ActionTypeValue>> = vec![Rc::new(
+            Transaction::new::<String>(
+                None,
+                date,
+                vec![
+                    TransactionElement::new(
+                        bank.id,
+                        Some(Commodity::from_str("10 AAPL").unwrap()),
+                        None,
+                    )
+                    .with_cost(Commodity::from_str("150 AUD").unwrap()),
+                    TransactionElement::new(broker.id, None, None),
+                ],
+            )
+            .into(),
+        )];
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        match program_state.execute_program(&Program::new(actions)) {
+            Err(AccountingError::InvalidTransaction(_, _)) => {}
+            _ => panic!("Expected an AccountingError::InvalidTransaction"),
+        }
+    }
+
+    #[test]
+    fn rounding_residual() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let expenses = Rc::from(Account::new_with_id(Some("Expenses"), aud.id, None));
+        let rounding = Rc::from(Account::new_with_id(Some("Rounding"), aud.id, None));
+        let accounts = vec![bank.clone(), expenses.clone(), rounding.clone()];
+
+        let transaction = |paid: &str, spent: &str| -> Rc<ActionTypeValue> {
             Rc::new(
-                BalanceAssertion::new(
-                    account2.id,
-                    date_2.clone(),
-                    Commodity::new(Decimal::new(100, 2), &*aud),
+                Transaction::new::<String>(
+                    None,
+                    date,
+                    vec![
+                        TransactionElement::new(
+                            bank.id,
+                            Some(Commodity::from_str(paid).unwrap()),
+                            None,
+                        ),
+                        TransactionElement::new(
+                            expenses.id,
+                            Some(Commodity::from_str(spent).unwrap()),
+                            None,
+                        ),
+                    ],
                 )
                 .into(),
-            ),
-        ];
+            )
+        };
 
-        let program = Program::new(actions);
+        let execute = |action: Rc<ActionTypeValue>| {
+            let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+            program_state.rounding_account_id = Some(rounding.id);
+            program_state
+                .rounding_tolerances
+                .insert(aud.id, Decimal::from_str("0.01").unwrap());
+            program_state
+                .execute_program(&Program::new(vec![action]))
+                .map(|_| program_state)
+        };
 
-        let accounts = vec![account1, account2];
-        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
-        match program_state.execute_program(&program) {
-            Err(AccountingError::BalanceAssertionFailed(failure)) => {
-                assert_eq!(
-                    Commodity::new(Decimal::new(0, 2), &*aud),
-                    failure.actual_balance
-                );
-                assert_eq!(date_1, failure.assertion.date);
-            }
-            _ => panic!("Expected an AccountingError:BalanceAssertionFailed"),
+        let program_state = execute(transaction("-10.00 AUD", "9.99 AUD")).unwrap();
+        assert_eq!(
+            Commodity::from_str("0.01 AUD").unwrap(),
+            program_state
+                .get_account_state(&rounding.id)
+                .unwrap()
+                .amount
+        );
+        let residual = program_state.journal[0].elements.last().unwrap();
+        assert_eq!(rounding.id, residual.account_id);
+        assert_eq!(
+            Some(Commodity::from_str("0.01 AUD").unwrap()),
+            residual.amount
+        );
+
+        match execute(transaction("-10.00 AUD", "9.98 AUD")) {
+            Err(AccountingError::InvalidTransaction(_, _)) => {}
+            _ => panic!("Expected an AccountingError::InvalidTransaction"),
         }
+    }
 
-        assert_eq!(1, program_state.failed_balance_assertions.len());
+    #[test]
+    fn account_status_directions() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let income = Rc::from(Account::new_with_id(Some("Income"), aud.id, None));
+        let accounts = vec![bank.clone(), income.clone()];
+
+        let transfer = |from, to, amount| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new_simple::<String>(
+                    None,
+                    date,
+                    from,
+                    to,
+                    Commodity::from_str(amount).unwrap(),
+                    None,
+                )
+                .into(),
+            )
+        };
+        let execute = |status, action: Rc<ActionTypeValue>| {
+            let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+            program_state
+                .execute_program(&Program::new(vec![transfer(income.id, bank.id, "10 AUD")]))
+                .unwrap();
+            program_state
+                .get_account_state_mut(&bank.id)
+                .unwrap()
+                .set_status(status, date);
+            program_state.execute_program(&Program::new(vec![action]))
+        };
+
+        let deposit = transfer(income.id, bank.id, "5 AUD");
+        let withdraw = transfer(bank.id, income.id, "5 AUD");
+        let overdraw = transfer(bank.id, income.id, "15 AUD");
+
+        assert!(execute(AccountStatus::ReceiveOnly, deposit.clone()).is_ok());
+        assert!(execute(AccountStatus::SendOnly, withdraw.clone()).is_ok());
+        assert!(execute(AccountStatus::PendingClose, withdraw.clone()).is_ok());
+
+        for (status, action, expected_direction) in [
+            (
+                AccountStatus::Frozen,
+                deposit.clone(),
+                PostingDirection::Incoming,
+            ),
+            (
+                AccountStatus::Frozen,
+                withdraw.clone(),
+                PostingDirection::Outgoing,
+            ),
+            (
+                AccountStatus::ReceiveOnly,
+                withdraw,
+                PostingDirection::Outgoing,
+            ),
+            (
+                AccountStatus::SendOnly,
+                deposit.clone(),
+                PostingDirection::Incoming,
+            ),
+            (
+                AccountStatus::PendingClose,
+                deposit,
+                PostingDirection::Incoming,
+            ),
+            (
+                AccountStatus::PendingClose,
+                overdraw,
+                PostingDirection::Outgoing,
+            ),
+        ] {
+            match execute(status, action) {
+                Err(AccountingError::InvalidAccountStatus {
+                    account_id,
+                    status: error_status,
+                    direction,
+                }) => {
+                    assert_eq!(bank.id, account_id);
+                    assert_eq!(status, error_status);
+                    assert_eq!(expected_direction, direction);
+                }
+                _ => panic!("Expected an AccountingError::InvalidAccountStatus"),
+            }
+        }
     }
 }
 
 #[cfg(feature = "serde-support")]
 #[cfg(test)]
 mod serde_tests {
-    use super::{BalanceAssertion, EditAccountStatus, Transaction};
-    use crate::{AccountID, AccountStatus};
+    use super::{AccrueInterest, BalanceAssertion, EditAccountStatus, Pad, Price, Transaction};
+    use crate::{AccountID, AccountStatus, DayCount, InterestMethod, RoundingPolicy};
     use chrono::NaiveDate;
-    use commodity::Commodity;
+    use commodity::{Commodity, CommodityTypeID};
+    use rust_decimal::{Decimal, RoundingStrategy};
     use std::str::FromStr;
 
     #[test]
@@ -781,7 +3173,7 @@ mod serde_tests {
         let reference_action = EditAccountStatus::new(
             AccountID::from("TestAccount").unwrap(),
             AccountStatus::Open,
-            NaiveDate::from_ymd(2020, 05, 10),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
         );
 
         assert_eq!(action, reference_action);
@@ -805,7 +3197,7 @@ mod serde_tests {
 
         let reference_action = BalanceAssertion::new(
             AccountID::from("TestAccount").unwrap(),
-            NaiveDate::from_ymd(2020, 05, 10),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
             Commodity::from_str("1.0 AUD").unwrap(),
         );
 
@@ -814,6 +3206,134 @@ mod serde_tests {
         insta::assert_json_snapshot!(action);
     }
 
+    #[test]
+    fn balance_assertion_comparison_serde() {
+        use serde_json;
+
+        let json = r#"{
+    "account_id": "TestAccount",
+    "date": "2020-05-10",
+    "expected_balance": {
+        "value": "0.0",
+        "type_id": "BTC"
+    },
+    "comparison": "AtLeast",
+    "tolerance": "0.00000001",
+    "include_subaccounts": true
+}"#;
+
+        let action: BalanceAssertion = serde_json::from_str(json).unwrap();
+
+        let reference_action = BalanceAssertion::at_least(
+            AccountID::from("TestAccount").unwrap(),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
+            Commodity::from_str("0.0 BTC").unwrap(),
+        )
+        .with_tolerance(Decimal::new(1, 8))
+        .with_subaccounts();
+
+        assert_eq!(action, reference_action);
+
+        insta::assert_json_snapshot!(action);
+    }
+
+    #[test]
+    fn price_serde() {
+        use serde_json;
+
+        let json = r#"{
+    "commodity_type_id": "AAPL",
+    "price": {
+        "value": "300.0",
+        "type_id": "USD"
+    },
+    "date": "2020-05-10"
+}"#;
+        let action: Price = serde_json::from_str(json).unwrap();
+
+        let reference_action = Price::new(
+            CommodityTypeID::from_str("AAPL").unwrap(),
+            Commodity::from_str("300.0 USD").unwrap(),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
+        );
+
+        assert_eq!(action, reference_action);
+
+        insta::assert_json_snapshot!(action);
+    }
+
+    #[test]
+    fn pad_serde() {
+        use serde_json;
+
+        let json = r#"{
+    "account_id": "TestAccount1",
+    "source_account_id": "TestAccount2",
+    "date": "2020-05-10"
+}"#;
+        let action: Pad = serde_json::from_str(json).unwrap();
+
+        let reference_action = Pad::new(
+            AccountID::from("TestAccount1").unwrap(),
+            AccountID::from("TestAccount2").unwrap(),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
+        );
+
+        assert_eq!(action, reference_action);
+
+        insta::assert_json_snapshot!(action);
+    }
+
+    #[test]
+    fn accrue_interest_serde() {
+        use serde_json;
+
+        let json = r#"{
+    "account_id": "TestAccount1",
+    "interest_account_id": "TestAccount2",
+    "annual_rate": "0.05",
+    "start_date": "2020-04-10",
+    "date": "2020-05-10",
+    "method": "Compound",
+    "day_count": "Thirty360"
+}"#;
+        let action: AccrueInterest = serde_json::from_str(json).unwrap();
+
+        let reference_action = AccrueInterest::new(
+            AccountID::from("TestAccount1").unwrap(),
+            AccountID::from("TestAccount2").unwrap(),
+            Decimal::new(5, 2),
+            NaiveDate::from_ymd_opt(2020, 4, 10).unwrap(),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
+        )
+        .with_method(InterestMethod::Compound)
+        .with_day_count(DayCount::Thirty360);
+
+        assert_eq!(action, reference_action);
+
+        insta::assert_json_snapshot!(action);
+    }
+
+    #[test]
+    fn accrue_interest_rounding_serde() {
+        use serde_json;
+
+        let action = AccrueInterest::new(
+            AccountID::from("TestAccount1").unwrap(),
+            AccountID::from("TestAccount2").unwrap(),
+            Decimal::new(5, 2),
+            NaiveDate::from_ymd_opt(2020, 4, 10).unwrap(),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
+        )
+        .with_rounding(RoundingPolicy::new(4, RoundingStrategy::ToZero));
+
+        let json = serde_json::to_string(&action).unwrap();
+        let deserialized: AccrueInterest = serde_json::from_str(&json).unwrap();
+        assert_eq!(action, deserialized);
+
+        insta::assert_json_snapshot!(action);
+    }
+
     #[cfg(feature = "serde-support")]
     #[test]
     fn transaction_serde() {
@@ -839,7 +3359,7 @@ mod serde_tests {
 
         let reference_action = Transaction::new_simple(
             Some("TestTransaction"),
-            NaiveDate::from_ymd(2020, 05, 10),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
             AccountID::from("TestAccount1").unwrap(),
             AccountID::from("TestAccount2").unwrap(),
             Commodity::from_str("1.0 AUD").unwrap(),
@@ -850,4 +3370,60 @@ mod serde_tests {
 
         insta::assert_json_snapshot!(action);
     }
+
+    #[test]
+    fn transaction_metadata_serde() {
+        use serde_json;
+
+        let json = r#"{
+    "description": "Invoice payment",
+    "date": "2020-05-10",
+    "elements": [
+        {
+            "account_id": "Bank",
+            "amount": {
+                "value": "-100.0",
+                "type_id": "AUD"
+            },
+            "memo": "Direct debit",
+            "metadata": {
+                "reference": "DD-123"
+            }
+        },
+        {
+            "account_id": "Payable"
+        }
+    ],
+    "payee": "Acme",
+    "tags": ["business"],
+    "links": ["invoice-42"],
+    "metadata": {
+        "invoice_id": "42"
+    }
+}"#;
+        let action: Transaction = serde_json::from_str(json).unwrap();
+
+        let mut reference_action = Transaction::new_simple(
+            Some("Invoice payment"),
+            NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
+            AccountID::from("Bank").unwrap(),
+            AccountID::from("Payable").unwrap(),
+            Commodity::from_str("100.0 AUD").unwrap(),
+            None,
+        )
+        .with_payee("Acme")
+        .with_tag("business")
+        .with_link("invoice-42")
+        .with_metadata("invoice_id", "42");
+        reference_action.elements[0] = reference_action.elements[0]
+            .clone()
+            .with_memo("Direct debit")
+            .with_metadata("reference", "DD-123");
+
+        assert_eq!(action, reference_action);
+        assert!(action.has_tag("business"));
+        assert_eq!(Some("42"), action.get_metadata("invoice_id"));
+
+        insta::assert_json_snapshot!(action);
+    }
 }
@@ -0,0 +1,337 @@
+use super::{AccountID, Disposal};
+use chrono::{Months, NaiveDate};
+use commodity::{Commodity, CommodityTypeID};
+use rust_decimal::Decimal;
+
+/// The classification of a [Disposal](Disposal) by how long the
+/// disposed [Lot](crate::Lot) was held for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HoldingTerm {
+    /// The lot was held for less than the holding period threshold.
+    ShortTerm,
+    /// The lot was held for at least the holding period threshold.
+    LongTerm,
+}
+
+/// The threshold used to classify a [Disposal](Disposal) as
+/// [ShortTerm](HoldingTerm::ShortTerm) or
+/// [LongTerm](HoldingTerm::LongTerm).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HoldingPeriod {
+    /// The number of months a lot needs to be held for.
+    pub months: u32,
+    /// Whether a lot disposed of exactly `months` after it was
+    /// acquired is considered to be long term. If this is `false`,
+    /// the lot needs to be held for more than `months`.
+    pub inclusive: bool,
+}
+
+impl HoldingPeriod {
+    /// A lot is long term if it is held for more than `months`
+    /// (e.g. the United States).
+    pub fn more_than(months: u32) -> HoldingPeriod {
+        HoldingPeriod {
+            months,
+            inclusive: false,
+        }
+    }
+
+    /// A lot is long term if it is held for at least `months`
+    /// (e.g. Australia).
+    pub fn at_least(months: u32) -> HoldingPeriod {
+        HoldingPeriod {
+            months,
+            inclusive: true,
+        }
+    }
+
+    /// Classify a lot acquired on `acquisition_date` and disposed of
+    /// on `disposal_date`.
+    pub fn classify(&self, acquisition_date: NaiveDate, disposal_date: NaiveDate) -> HoldingTerm {
+        let threshold = match acquisition_date.checked_add_months(Months::new(self.months)) {
+            Some(threshold) => threshold,
+            None => return HoldingTerm::ShortTerm,
+        };
+
+        let long_term = if self.inclusive {
+            disposal_date >= threshold
+        } else {
+            disposal_date > threshold
+        };
+
+        if long_term {
+            HoldingTerm::LongTerm
+        } else {
+            HoldingTerm::ShortTerm
+        }
+    }
+}
+
+/// A hook to adjust the gain realized by a [Disposal](Disposal) to
+/// calculate the taxable gain, used to implement the discount rules
+/// of a particular jurisdiction.
+pub trait GainAdjustment {
+    /// Calculate the taxable gain for the `disposal`, which was
+    /// classified using the given `term`.
+    fn taxable_gain(&self, disposal: &Disposal, term: HoldingTerm) -> Commodity;
+}
+
+/// A [GainAdjustment](GainAdjustment) where the taxable gain is
+/// equal to the realized gain.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoAdjustment;
+
+impl GainAdjustment for NoAdjustment {
+    fn taxable_gain(&self, disposal: &Disposal, _term: HoldingTerm) -> Commodity {
+        disposal.gain
+    }
+}
+
+/// A [GainAdjustment](GainAdjustment) which discounts
+/// [LongTerm](HoldingTerm::LongTerm) gains by a proportion (e.g. `0.5`
+/// for a 50% discount). Losses are not discounted.
+#[derive(Copy, Clone, Debug)]
+pub struct LongTermDiscount {
+    /// The proportion of a long term gain which is not taxable.
+    pub discount: Decimal,
+}
+
+impl GainAdjustment for LongTermDiscount {
+    fn taxable_gain(&self, disposal: &Disposal, term: HoldingTerm) -> Commodity {
+        if term == HoldingTerm::LongTerm && disposal.gain.value.is_sign_positive() {
+            Commodity::new(
+                disposal.gain.value * (Decimal::new(1, 0) - self.discount),
+                disposal.gain.type_id,
+            )
+        } else {
+            disposal.gain
+        }
+    }
+}
+
+/// An entry for a single [Disposal](Disposal) in a
+/// [CapitalGainsReport](CapitalGainsReport).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapitalGainsEntry {
+    /// The id of the account that the lot was held in.
+    pub account_id: AccountID,
+    /// The label of the lot.
+    pub lot_label: Option<String>,
+    /// The quantity disposed of.
+    pub quantity: Commodity,
+    /// The date that the lot was acquired.
+    pub acquisition_date: NaiveDate,
+    /// The date of the disposal.
+    pub disposal_date: NaiveDate,
+    /// The amount received for the quantity disposed of.
+    pub proceeds: Commodity,
+    /// The cost of the quantity disposed of.
+    pub cost_basis: Commodity,
+    /// The realized gain (negative for a loss).
+    pub gain: Commodity,
+    /// The classification of the holding period.
+    pub term: HoldingTerm,
+    /// The gain after applying the [GainAdjustment](GainAdjustment).
+    pub taxable_gain: Commodity,
+}
+
+/// A report of the capital gains realized by the disposal of
+/// [Lot](crate::Lot)s within a period (e.g. a tax year), derived from
+/// the `disposals` recorded in a [ProgramState](crate::ProgramState).
+///
+/// # Example
+/// ```
+/// use doublecount::{CapitalGainsReport, Disposal, HoldingPeriod, HoldingTerm, NoAdjustment, AccountID};
+/// use commodity::{Commodity, CommodityTypeID};
+/// use chrono::NaiveDate;
+/// use std::str::FromStr;
+///
+/// let disposal = Disposal {
+///     account_id: AccountID::from("Brokerage").unwrap(),
+///     quantity: Commodity::from_str("10 AAPL").unwrap(),
+///     acquisition_date: NaiveDate::from_ymd_opt(2019, 1, 1).unwrap(),
+///     disposal_date: NaiveDate::from_ymd_opt(2020, 3, 1).unwrap(),
+///     lot_label: None,
+///     cost_basis: Commodity::from_str("1000 USD").unwrap(),
+///     proceeds: Commodity::from_str("3000 USD").unwrap(),
+///     gain: Commodity::from_str("2000 USD").unwrap(),
+/// };
+///
+/// let report = CapitalGainsReport::new(
+///     &[disposal],
+///     NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+///     NaiveDate::from_ymd_opt(2020, 12, 31).unwrap(),
+///     HoldingPeriod::more_than(12),
+///     &NoAdjustment,
+/// );
+///
+/// assert_eq!(HoldingTerm::LongTerm, report.entries[0].term);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapitalGainsReport {
+    /// The first date of the period covered by the report.
+    pub start_date: NaiveDate,
+    /// The last date of the period covered by the report.
+    pub end_date: NaiveDate,
+    /// An entry for each disposal within the period, ordered by
+    /// disposal date.
+    pub entries: Vec<CapitalGainsEntry>,
+}
+
+impl CapitalGainsReport {
+    /// Create a new [CapitalGainsReport](CapitalGainsReport) from the
+    /// `disposals` made between `start_date` and `end_date`
+    /// (inclusive), classified using the `holding_period` and
+    /// adjusted using the `adjustment`.
+    pub fn new(
+        disposals: &[Disposal],
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+        holding_period: HoldingPeriod,
+        adjustment: &dyn GainAdjustment,
+    ) -> CapitalGainsReport {
+        let mut entries: Vec<CapitalGainsEntry> = disposals
+            .iter()
+            .filter(|d| d.disposal_date >= start_date && d.disposal_date <= end_date)
+            .map(|disposal| {
+                let term =
+                    holding_period.classify(disposal.acquisition_date, disposal.disposal_date);
+                CapitalGainsEntry {
+                    account_id: disposal.account_id,
+                    lot_label: disposal.lot_label.clone(),
+                    quantity: disposal.quantity,
+                    acquisition_date: disposal.acquisition_date,
+                    disposal_date: disposal.disposal_date,
+                    proceeds: disposal.proceeds,
+                    cost_basis: disposal.cost_basis,
+                    gain: disposal.gain,
+                    term,
+                    taxable_gain: adjustment.taxable_gain(disposal, term),
+                }
+            })
+            .collect();
+
+        entries.sort_by_key(|entry| entry.disposal_date);
+
+        CapitalGainsReport {
+            start_date,
+            end_date,
+            entries,
+        }
+    }
+
+    /// The sum of the gains in the commodity type
+    /// `commodity_type_id`, optionally only for the given `term`.
+    pub fn total_gain(
+        &self,
+        commodity_type_id: CommodityTypeID,
+        term: Option<HoldingTerm>,
+    ) -> Commodity {
+        self.sum(commodity_type_id, term, |entry| entry.gain)
+    }
+
+    /// The sum of the taxable gains in the commodity type
+    /// `commodity_type_id`, optionally only for the given `term`.
+    pub fn total_taxable_gain(
+        &self,
+        commodity_type_id: CommodityTypeID,
+        term: Option<HoldingTerm>,
+    ) -> Commodity {
+        self.sum(commodity_type_id, term, |entry| entry.taxable_gain)
+    }
+
+    fn sum<F: Fn(&CapitalGainsEntry) -> Commodity>(
+        &self,
+        commodity_type_id: CommodityTypeID,
+        term: Option<HoldingTerm>,
+        value: F,
+    ) -> Commodity {
+        let total = self
+            .entries
+            .iter()
+            .filter(|entry| term.map(|t| t == entry.term).unwrap_or(true))
+            .map(value)
+            .filter(|v| v.type_id == commodity_type_id)
+            .map(|v| v.value)
+            .sum();
+        Commodity::new(total, commodity_type_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CapitalGainsReport, HoldingPeriod, HoldingTerm, LongTermDiscount};
+    use crate::{AccountID, Disposal};
+    use chrono::NaiveDate;
+    use commodity::{Commodity, CommodityTypeID};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn disposal(acquisition_date: NaiveDate, disposal_date: NaiveDate, gain: &str) -> Disposal {
+        let gain = Commodity::from_str(gain).unwrap();
+        let cost_basis = Commodity::from_str("1000 USD").unwrap();
+        Disposal {
+            account_id: AccountID::from("Brokerage").unwrap(),
+            quantity: Commodity::from_str("10 AAPL").unwrap(),
+            acquisition_date,
+            disposal_date,
+            lot_label: None,
+            cost_basis,
+            proceeds: cost_basis.add(&gain).unwrap(),
+            gain,
+        }
+    }
+
+    #[test]
+    fn holding_period() {
+        let acquired = NaiveDate::from_ymd_opt(2019, 1, 31).unwrap();
+        let one_year = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+
+        assert_eq!(
+            HoldingTerm::ShortTerm,
+            HoldingPeriod::more_than(12).classify(acquired, one_year)
+        );
+        assert_eq!(
+            HoldingTerm::LongTerm,
+            HoldingPeriod::at_least(12).classify(acquired, one_year)
+        );
+    }
+
+    #[test]
+    fn capital_gains_report() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        let disposals = vec![
+            disposal(date(2019, 1, 1), date(2020, 6, 1), "2000 USD"),
+            disposal(date(2020, 1, 1), date(2020, 3, 1), "500 USD"),
+            disposal(date(2020, 1, 1), date(2020, 4, 1), "-300 USD"),
+            disposal(date(2020, 1, 1), date(2021, 3, 1), "700 USD"),
+        ];
+
+        let report = CapitalGainsReport::new(
+            &disposals,
+            date(2020, 1, 1),
+            date(2020, 12, 31),
+            HoldingPeriod::at_least(12),
+            &LongTermDiscount {
+                discount: Decimal::new(5, 1),
+            },
+        );
+
+        assert_eq!(3, report.entries.len());
+        assert_eq!(date(2020, 3, 1), report.entries[0].disposal_date);
+        assert_eq!(
+            Commodity::from_str("2200 USD").unwrap(),
+            report.total_gain(usd, None)
+        );
+        assert_eq!(
+            Commodity::from_str("200 USD").unwrap(),
+            report.total_gain(usd, Some(HoldingTerm::ShortTerm))
+        );
+        assert_eq!(
+            Commodity::from_str("1200 USD").unwrap(),
+            report.total_taxable_gain(usd, None)
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use super::{AccountID, Transaction};
+use commodity::Commodity;
+use std::fmt;
+
+#[cfg(feature = "serde-support")]
+use serde::{Deserialize, Serialize};
+
+/// A constraint on an [Account](crate::Account), which is checked
+/// whenever a [Transaction](Transaction) modifies the account.
+///
+/// How a violation of a constraint is handled depends on the
+/// [ConstraintMode](ConstraintMode) of the
+/// [ProgramState](crate::ProgramState).
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountConstraint {
+    /// The balance of the account can never be negative (e.g. no
+    /// overdraft).
+    NonNegative,
+    /// The balance of the account can never be less than the given
+    /// amount. For a liability account such as a credit card (with a
+    /// negative balance), this is the negated credit limit. Only the
+    /// balance of the commodity type of the amount is constrained.
+    MinimumBalance(Commodity),
+    /// The balance of the account can never be more than the given
+    /// amount. Only the balance of the commodity type of the amount
+    /// is constrained.
+    MaximumBalance(Commodity),
+    /// Only positive amounts (debits) can be posted to the account.
+    DebitsOnly,
+    /// Only negative amounts (credits) can be posted to the account.
+    CreditsOnly,
+}
+
+impl AccountConstraint {
+    /// Returns true if posting the `amount` to the account, resulting
+    /// in its `balance` (of the same commodity type as the `amount`),
+    /// satisfies this constraint.
+    pub fn is_satisfied(&self, amount: Commodity, balance: Commodity) -> bool {
+        if self.constrains_balance() {
+            self.is_satisfied_by_balance(balance)
+        } else {
+            self.is_satisfied_by_amount(amount)
+        }
+    }
+
+    /// Returns true if this constraint applies to the balance of the
+    /// account, rather than to each amount posted to it.
+    pub fn constrains_balance(&self) -> bool {
+        match self {
+            AccountConstraint::NonNegative
+            | AccountConstraint::MinimumBalance(_)
+            | AccountConstraint::MaximumBalance(_) => true,
+            AccountConstraint::DebitsOnly | AccountConstraint::CreditsOnly => false,
+        }
+    }
+
+    /// Returns true if the `balance` of the account satisfies this
+    /// constraint, always true for constraints which do not
+    /// [constrain the balance](AccountConstraint::constrains_balance()).
+    pub fn is_satisfied_by_balance(&self, balance: Commodity) -> bool {
+        match self {
+            AccountConstraint::NonNegative => !balance.value.is_sign_negative(),
+            AccountConstraint::MinimumBalance(minimum) => {
+                balance.type_id != minimum.type_id || balance.value >= minimum.value
+            }
+            AccountConstraint::MaximumBalance(maximum) => {
+                balance.type_id != maximum.type_id || balance.value <= maximum.value
+            }
+            AccountConstraint::DebitsOnly | AccountConstraint::CreditsOnly => true,
+        }
+    }
+
+    /// Returns true if posting the `amount` to the account satisfies
+    /// this constraint, always true for constraints which
+    /// [constrain the balance](AccountConstraint::constrains_balance()).
+    pub fn is_satisfied_by_amount(&self, amount: Commodity) -> bool {
+        match self {
+            AccountConstraint::DebitsOnly => !amount.value.is_sign_negative(),
+            AccountConstraint::CreditsOnly => !amount.value.is_sign_positive(),
+            AccountConstraint::NonNegative
+            | AccountConstraint::MinimumBalance(_)
+            | AccountConstraint::MaximumBalance(_) => true,
+        }
+    }
+}
+
+/// How violations of [AccountConstraint](AccountConstraint)s are
+/// handled while a [Program](crate::Program) is being executed.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum ConstraintMode {
+    /// The transaction which would violate a constraint fails with an
+    /// [AccountConstraintViolated](crate::AccountingError::AccountConstraintViolated)
+    /// error, without modifying any accounts.
+    #[default]
+    Error,
+    /// The transaction is performed, and the violation is recorded in
+    /// the [ProgramState](crate::ProgramState)'s
+    /// `constraint_violations`.
+    Record,
+}
+
+/// Records a [Transaction](Transaction) which violated an
+/// [AccountConstraint](AccountConstraint).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintViolation {
+    /// The id of the account with the constraint.
+    pub account_id: AccountID,
+    /// The constraint which was violated.
+    pub constraint: AccountConstraint,
+    /// The transaction which violated the constraint.
+    pub transaction: Transaction,
+    /// The balance of the account after the transaction.
+    pub balance: Commodity,
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the transaction on {} violates the constraint {:?} of account {}, resulting in a balance of {}",
+            self.transaction.date, self.constraint, self.account_id, self.balance
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountConstraint, ConstraintMode};
+    use crate::{
+        Account, AccountStatus, AccountingError, ActionTypeValue, Program, ProgramState,
+        Transaction, TransactionElement,
+    };
+    use chrono::NaiveDate;
+    use commodity::{Commodity, CommodityType};
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    #[test]
+    fn account_constraints() {
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let income = Rc::from(
+            Account::new_with_id(Some("Income"), aud.id, None)
+                .with_constraint(AccountConstraint::CreditsOnly),
+        );
+        let wallet = Rc::from(
+            Account::new_with_id(Some("Wallet"), aud.id, None)
+                .with_constraint(AccountConstraint::NonNegative),
+        );
+        let credit_card = Rc::from(
+            Account::new_with_id(Some("Credit Card"), aud.id, None).with_constraint(
+                AccountConstraint::MinimumBalance(Commodity::from_str("-500 AUD").unwrap()),
+            ),
+        );
+        let expenses = Rc::from(Account::new_with_id(Some("Expenses"), aud.id, None));
+        let accounts = vec![
+            income.clone(),
+            wallet.clone(),
+            credit_card.clone(),
+            expenses.clone(),
+        ];
+
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let transaction = |from, to, amount| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new_simple::<String>(
+                    None,
+                    date,
+                    from,
+                    to,
+                    Commodity::from_str(amount).unwrap(),
+                    None,
+                )
+                .into(),
+            )
+        };
+
+        let program = Program::new(vec![
+            transaction(income.id, wallet.id, "100 AUD"),
+            transaction(wallet.id, expenses.id, "80 AUD"),
+            transaction(credit_card.id, expenses.id, "500 AUD"),
+            // overdraws the wallet
+            transaction(wallet.id, expenses.id, "30 AUD"),
+        ]);
+
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        match program_state.execute_program(&program) {
+            Err(AccountingError::AccountConstraintViolated(violation)) => {
+                assert_eq!(wallet.id, violation.account_id);
+                assert_eq!(AccountConstraint::NonNegative, violation.constraint);
+                assert_eq!(Commodity::from_str("-10 AUD").unwrap(), violation.balance);
+            }
+            _ => panic!("Expected an AccountingError::AccountConstraintViolated"),
+        }
+        // the violating transaction was not performed
+        assert_eq!(
+            Commodity::from_str("20 AUD").unwrap(),
+            program_state.get_account_state(&wallet.id).unwrap().amount
+        );
+
+        let program = Program::new(vec![
+            transaction(wallet.id, income.id, "10 AUD"),
+            transaction(credit_card.id, expenses.id, "501 AUD"),
+        ]);
+
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.constraint_mode = ConstraintMode::Record;
+        program_state.execute_program(&program).unwrap();
+
+        let violations: Vec<(_, AccountConstraint)> = program_state
+            .constraint_violations
+            .iter()
+            .map(|violation| (violation.account_id, violation.constraint.clone()))
+            .collect();
+        assert_eq!(
+            vec![
+                (wallet.id, AccountConstraint::NonNegative),
+                (income.id, AccountConstraint::CreditsOnly),
+                (
+                    credit_card.id,
+                    AccountConstraint::MinimumBalance(Commodity::from_str("-500 AUD").unwrap())
+                ),
+            ],
+            violations
+        );
+
+        // the balance of an account with two elements in a transaction
+        // is only checked once
+        let program: Program = Program::new(vec![Rc::new(
+            Transaction::new::<String>(
+                None,
+                date,
+                vec![
+                    TransactionElement::new(
+                        wallet.id,
+                        Some(Commodity::from_str("-5 AUD").unwrap()),
+                        None,
+                    ),
+                    TransactionElement::new(
+                        wallet.id,
+                        Some(Commodity::from_str("-5 AUD").unwrap()),
+                        None,
+                    ),
+                    TransactionElement::new(expenses.id, None, None),
+                ],
+            )
+            .into(),
+        )]);
+
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.constraint_mode = ConstraintMode::Record;
+        program_state.execute_program(&program).unwrap();
+        assert_eq!(1, program_state.constraint_violations.len());
+        assert_eq!(
+            Commodity::from_str("-10 AUD").unwrap(),
+            program_state.constraint_violations[0].balance
+        );
+    }
+
+    #[test]
+    fn inventory_account_constraints() {
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let margin = Rc::from(
+            Account::new_with_id(Some("Margin"), aud.id, None)
+                .with_inventory()
+                .with_constraint(AccountConstraint::MinimumBalance(
+                    Commodity::from_str("-500 AUD").unwrap(),
+                )),
+        );
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let accounts = vec![margin.clone(), bank.clone()];
+
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let buy = |shares: &str, cost: &str| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new::<String>(
+                    None,
+                    date,
+                    vec![
+                        TransactionElement::new(
+                            margin.id,
+                            Some(Commodity::from_str(shares).unwrap()),
+                            None,
+                        )
+                        .with_cost(Commodity::from_str(cost).unwrap()),
+                        TransactionElement::new(margin.id, None, None),
+                    ],
+                )
+                .into(),
+            )
+        };
+
+        // buying shares on margin within the limit
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        program_state
+            .execute_program(&Program::new(vec![buy("4 AAPL", "100 AUD")]))
+            .unwrap();
+        assert!(program_state.constraint_violations.is_empty());
+
+        let mut program_state = ProgramState::new(&accounts, AccountStatus::Open);
+        match program_state.execute_program(&Program::new(vec![buy("6 AAPL", "100 AUD")])) {
+            Err(AccountingError::AccountConstraintViolated(violation)) => {
+                assert_eq!(margin.id, violation.account_id);
+                assert_eq!(Commodity::from_str("-600 AUD").unwrap(), violation.balance);
+            }
+            _ => panic!("Expected an AccountingError::AccountConstraintViolated"),
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use super::{AccountID, AccountState, AccountingError, PriceDatabase, PriceLookup};
+use chrono::NaiveDate;
+use commodity::exchange_rate::ExchangeRate;
+use commodity::{Commodity, CommodityTypeID};
+use rust_decimal::Decimal;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Whether an edge in a [RateGraph](RateGraph) was supplied directly,
+/// or derived by inverting a supplied rate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum RateKind {
+    Direct,
+    Inverse,
+}
+
+/// The preference to use when searching for a path through a
+/// [RateGraph](RateGraph) to convert between two
+/// [CommodityType](commodity::CommodityType)s.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathPreference {
+    /// Only allow a conversion using a single rate between the two
+    /// commodity types (which may be the inverse of a supplied rate).
+    DirectOnly,
+    /// Use the path with the fewest hops, optionally limited to
+    /// `max_hops`. When there are several paths with the same number
+    /// of hops, the path which uses the most rates in the direction
+    /// they were supplied (rather than inverted) is preferred.
+    FewestHops { max_hops: Option<usize> },
+}
+
+impl Default for PathPreference {
+    fn default() -> Self {
+        PathPreference::FewestHops { max_hops: None }
+    }
+}
+
+/// The result of converting a [Commodity](Commodity) using a
+/// [RateGraph](RateGraph), including a report of the path of
+/// commodity types that was used to perform the conversion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    /// The commodity before conversion.
+    pub from: Commodity,
+    /// The commodity after conversion.
+    pub to: Commodity,
+    /// The effective rate used for the conversion, the number of
+    /// units of `to` for one unit of `from`.
+    pub rate: Decimal,
+    /// The commodity types visited during the conversion, starting
+    /// with the type of `from` and ending with the type of `to`.
+    pub path: Vec<CommodityTypeID>,
+}
+
+/// A graph of known exchange rates between
+/// [CommodityType](commodity::CommodityType)s, used to convert between
+/// commodity types which have no direct rate, by finding a path
+/// through other commodity types (e.g. `NZD -> USD -> EUR`).
+///
+/// # Example
+/// ```
+/// use doublecount::{RateGraph, PathPreference};
+/// use commodity::{Commodity, CommodityTypeID};
+/// use rust_decimal::Decimal;
+/// use std::str::FromStr;
+///
+/// let nzd = CommodityTypeID::from_str("NZD").unwrap();
+/// let usd = CommodityTypeID::from_str("USD").unwrap();
+/// let eur = CommodityTypeID::from_str("EUR").unwrap();
+///
+/// let mut graph = RateGraph::new();
+/// graph.add_rate(nzd, usd, Decimal::from_str("0.5").unwrap());
+/// graph.add_rate(usd, eur, Decimal::from_str("0.8").unwrap());
+///
+/// let conversion = graph
+///     .convert(
+///         Commodity::from_str("10.0 NZD").unwrap(),
+///         eur,
+///         PathPreference::default(),
+///     )
+///     .unwrap();
+///
+/// assert_eq!(Commodity::from_str("4.0 EUR").unwrap(), conversion.to);
+/// assert_eq!(vec![nzd, usd, eur], conversion.path);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RateGraph {
+    /// Maps a commodity type id to the rates for converting one unit
+    /// of it into each of its neighbouring commodity types.
+    edges: HashMap<CommodityTypeID, HashMap<CommodityTypeID, (Decimal, RateKind)>>,
+}
+
+impl RateGraph {
+    /// Create a new empty [RateGraph](RateGraph).
+    pub fn new() -> RateGraph {
+        RateGraph::default()
+    }
+
+    /// Create a new [RateGraph](RateGraph) using all the prices in a
+    /// [PriceDatabase](PriceDatabase) which are effective on the given
+    /// `date`.
+    pub fn from_price_database(
+        price_database: &PriceDatabase,
+        date: NaiveDate,
+        lookup: PriceLookup,
+    ) -> RateGraph {
+        let mut graph = RateGraph::new();
+        for (base, quote) in price_database.pairs() {
+            if let Some(rate) = price_database.get_rate(base, quote, date, lookup) {
+                graph.add_rate(*base, *quote, rate);
+            }
+        }
+        graph
+    }
+
+    /// Add a `rate` to convert one unit of the commodity type `from`
+    /// into the commodity type `to`. The inverse rate is also added,
+    /// unless a rate has already been supplied in that direction.
+    pub fn add_rate(&mut self, from: CommodityTypeID, to: CommodityTypeID, rate: Decimal) {
+        if from == to {
+            return;
+        }
+
+        self.edges
+            .entry(from)
+            .or_default()
+            .insert(to, (rate, RateKind::Direct));
+
+        if rate.is_zero() {
+            return;
+        }
+
+        let inverse = Decimal::new(1, 0) / rate;
+        let reverse_edges = self.edges.entry(to).or_default();
+        match reverse_edges.get(&from) {
+            Some((_, RateKind::Direct)) => {}
+            _ => {
+                reverse_edges.insert(from, (inverse, RateKind::Inverse));
+            }
+        }
+    }
+
+    /// Add all the rates contained within an
+    /// [ExchangeRate](ExchangeRate) to this graph.
+    pub fn add_exchange_rate(&mut self, exchange_rate: &ExchangeRate) {
+        match exchange_rate.base {
+            Some(base) => {
+                for (commodity_type_id, rate) in &exchange_rate.rates {
+                    self.add_rate(base, *commodity_type_id, *rate);
+                }
+            }
+            None => {
+                for from in exchange_rate.rates.keys() {
+                    for to in exchange_rate.rates.keys() {
+                        if let Ok(Some(rate)) = exchange_rate.rate_between(from, to) {
+                            self.add_rate(*from, *to, rate);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Find the path of commodity types to use for converting from
+    /// the commodity type `from` into the commodity type `to`, using
+    /// the given `preference`. Returns `None` if there is no suitable
+    /// path.
+    pub fn find_path(
+        &self,
+        from: CommodityTypeID,
+        to: CommodityTypeID,
+        preference: PathPreference,
+    ) -> Option<Vec<CommodityTypeID>> {
+        if from == to {
+            return Some(vec![from]);
+        }
+
+        let max_hops = match preference {
+            PathPreference::DirectOnly => Some(1),
+            PathPreference::FewestHops { max_hops } => max_hops,
+        };
+
+        // Dijkstra's algorithm, with a cost of (hops, inversions)
+        let mut best: HashMap<CommodityTypeID, (usize, usize)> = HashMap::new();
+        let mut previous: HashMap<CommodityTypeID, CommodityTypeID> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+
+        best.insert(from, (0, 0));
+        queue.push(Reverse((0, 0, from)));
+
+        while let Some(Reverse((hops, inversions, current))) = queue.pop() {
+            if current == to {
+                let mut path = vec![to];
+                let mut node = to;
+                while let Some(prev) = previous.get(&node) {
+                    path.push(*prev);
+                    node = *prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            if best.get(&current) != Some(&(hops, inversions)) {
+                continue;
+            }
+
+            if max_hops.map(|max| hops >= max).unwrap_or(false) {
+                continue;
+            }
+
+            if let Some(neighbours) = self.edges.get(&current) {
+                for (neighbour, (_, kind)) in neighbours {
+                    let cost = (hops + 1, inversions + (*kind == RateKind::Inverse) as usize);
+                    if best.get(neighbour).map(|b| cost < *b).unwrap_or(true) {
+                        best.insert(*neighbour, cost);
+                        previous.insert(*neighbour, current);
+                        queue.push(Reverse((cost.0, cost.1, *neighbour)));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Convert a `commodity` into the commodity type `to`, finding a
+    /// path through this graph using the given `preference`.
+    pub fn convert(
+        &self,
+        commodity: Commodity,
+        to: CommodityTypeID,
+        preference: PathPreference,
+    ) -> Result<Conversion, AccountingError> {
+        let path = self
+            .find_path(commodity.type_id, to, preference)
+            .ok_or(AccountingError::NoConversionPath(commodity, to))?;
+
+        let rate = path.windows(2).fold(Decimal::new(1, 0), |rate, pair| {
+            rate * self.edges[&pair[0]][&pair[1]].0
+        });
+
+        Ok(Conversion {
+            from: commodity,
+            to: commodity.convert(to, rate),
+            rate,
+            path,
+        })
+    }
+}
+
+/// The result of [sum_account_states_converted()](sum_account_states_converted()).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertedSum {
+    /// The sum of the values in all the accounts.
+    pub sum: Commodity,
+    /// The conversions which were performed for accounts which held
+    /// a different type of commodity to the sum.
+    pub conversions: HashMap<AccountID, Conversion>,
+    /// The conversions which were performed for the amounts held in
+    /// the `inventory` of [inventory](crate::Account::inventory)
+    /// accounts, by account and the type of commodity held.
+    pub inventory_conversions: HashMap<(AccountID, CommodityTypeID), Conversion>,
+}
+
+/// Sum the values in all the accounts into a single
+/// [Commodity](Commodity), using the supplied `rate_graph` to convert
+/// a type of commodity in an account to the
+/// [CommodityType](commodity::CommodityType) associated with the id
+/// `sum_commodity_type_id`, via other commodity types if required.
+pub fn sum_account_states_converted(
+    account_states: &HashMap<AccountID, AccountState>,
+    sum_commodity_type_id: CommodityTypeID,
+    rate_graph: &RateGraph,
+    preference: PathPreference,
+) -> Result<ConvertedSum, AccountingError> {
+    let mut sum = Commodity::zero(sum_commodity_type_id);
+    let mut conversions = HashMap::new();
+    let mut inventory_conversions = HashMap::new();
+
+    for (account_id, account_state) in account_states {
+        for amount in account_state.inventory.values() {
+            let account_amount = if amount.type_id != sum_commodity_type_id {
+                let conversion = rate_graph.convert(*amount, sum_commodity_type_id, preference)?;
+                let converted = conversion.to;
+                inventory_conversions.insert((*account_id, amount.type_id), conversion);
+                converted
+            } else {
+                *amount
+            };
+
+            sum = sum.add(&account_amount)?;
+        }
+
+        let account_amount = if account_state.amount.type_id != sum_commodity_type_id {
+            let conversion =
+                rate_graph.convert(account_state.amount, sum_commodity_type_id, preference)?;
+            let converted = conversion.to;
+            conversions.insert(*account_id, conversion);
+            converted
+        } else {
+            account_state.amount
+        };
+
+        sum = sum.add(&account_amount)?;
+    }
+
+    Ok(ConvertedSum {
+        sum,
+        conversions,
+        inventory_conversions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sum_account_states_converted, PathPreference, RateGraph};
+    use crate::{Account, AccountStatus, AccountingError, ProgramState};
+    use commodity::exchange_rate::ExchangeRate;
+    use commodity::{Commodity, CommodityTypeID};
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    #[test]
+    fn multi_hop_conversion() {
+        let nzd = CommodityTypeID::from_str("NZD").unwrap();
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let eur = CommodityTypeID::from_str("EUR").unwrap();
+        let aud = CommodityTypeID::from_str("AUD").unwrap();
+
+        let mut rates = BTreeMap::new();
+        rates.insert(nzd, Decimal::from_str("2.0").unwrap());
+        rates.insert(eur, Decimal::from_str("0.8").unwrap());
+
+        let mut graph = RateGraph::new();
+        graph.add_exchange_rate(&ExchangeRate {
+            date: None,
+            obtained_datetime: None,
+            base: Some(usd),
+            rates,
+        });
+
+        // NZD -> USD is the inverse of a supplied rate
+        let conversion = graph
+            .convert(
+                Commodity::from_str("10.0 NZD").unwrap(),
+                eur,
+                PathPreference::default(),
+            )
+            .unwrap();
+        assert_eq!(vec![nzd, usd, eur], conversion.path);
+        assert_eq!(Commodity::from_str("4.0 EUR").unwrap(), conversion.to);
+
+        match graph.convert(
+            Commodity::from_str("10.0 NZD").unwrap(),
+            eur,
+            PathPreference::DirectOnly,
+        ) {
+            Err(AccountingError::NoConversionPath(_, type_id)) => assert_eq!(eur, type_id),
+            _ => panic!("Expected an AccountingError::NoConversionPath"),
+        }
+
+        // a direct rate is preferred over the two hop path
+        graph.add_rate(nzd, eur, Decimal::from_str("0.5").unwrap());
+        let conversion = graph
+            .convert(
+                Commodity::from_str("10.0 NZD").unwrap(),
+                eur,
+                PathPreference::default(),
+            )
+            .unwrap();
+        assert_eq!(vec![nzd, eur], conversion.path);
+
+        assert!(graph
+            .find_path(nzd, aud, PathPreference::default())
+            .is_none());
+    }
+
+    #[test]
+    fn sum_converted() {
+        let nzd = CommodityTypeID::from_str("NZD").unwrap();
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let eur = CommodityTypeID::from_str("EUR").unwrap();
+
+        let account1 = Rc::from(Account::new_with_id(Some("Account 1"), nzd, None));
+        let account2 = Rc::from(Account::new_with_id(Some("Account 2"), eur, None));
+
+        let mut program_state: ProgramState =
+            ProgramState::new(&[account1.clone(), account2], AccountStatus::Open);
+        program_state
+            .get_account_state_mut(&account1.id)
+            .unwrap()
+            .amount = Commodity::from_str("10.0 NZD").unwrap();
+
+        let mut graph = RateGraph::new();
+        graph.add_rate(nzd, usd, Decimal::from_str("0.5").unwrap());
+        graph.add_rate(usd, eur, Decimal::from_str("0.8").unwrap());
+
+        let converted = sum_account_states_converted(
+            &program_state.account_states,
+            eur,
+            &graph,
+            PathPreference::default(),
+        )
+        .unwrap();
+
+        assert_eq!(Commodity::from_str("4.0 EUR").unwrap(), converted.sum);
+        assert_eq!(1, converted.conversions.len());
+        assert_eq!(
+            vec![nzd, usd, eur],
+            converted.conversions.get(&account1.id).unwrap().path
+        );
+    }
+}
@@ -0,0 +1,374 @@
+use super::{
+    AccountID, AccountingError, Recurrence, RoundingPolicy, Transaction, TransactionElement,
+    TransactionGenerator,
+};
+use chrono::{Duration, NaiveDate};
+use commodity::Commodity;
+use rust_decimal::Decimal;
+
+/// The type of amount being spread by a [Deferral](Deferral).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeferralKind {
+    /// An expense paid in advance (such as an insurance premium),
+    /// held in a prepaid asset account and recognized as an expense.
+    PrepaidExpense,
+    /// Revenue received in advance (such as a subscription), held in
+    /// an unearned revenue liability account and recognized as
+    /// revenue.
+    UnearnedRevenue,
+}
+
+/// How the amount of a [Deferral](Deferral) is divided between
+/// periods.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Proration {
+    /// In proportion to the number of days in each period.
+    Days,
+    /// Equally between each whole month, with a partial final month
+    /// in proportion to the number of days it covers.
+    Months,
+}
+
+/// The accounts involved in a [Deferral](Deferral).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DeferralAccounts {
+    /// The account holding the deferred amount (a prepaid asset, or
+    /// an unearned revenue liability).
+    pub deferral_account_id: AccountID,
+    /// The account that the amount is recognized in (an expense, or
+    /// a revenue account).
+    pub recognition_account_id: AccountID,
+}
+
+/// A row in the recognition table of a [Deferral](Deferral),
+/// describing a single period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeferralRow {
+    /// The number of the period, starting at `1`.
+    pub period: usize,
+    /// The first date of the period.
+    pub start_date: NaiveDate,
+    /// The last date of the period, which the amount is recognized
+    /// on.
+    pub end_date: NaiveDate,
+    /// The amount recognized for the period.
+    pub amount: Commodity,
+    /// The amount which remains deferred at the end of the period.
+    pub remaining: Commodity,
+}
+
+/// Spreads a single `amount` paid or received in advance, covering
+/// the dates from `start_date` (inclusive) until `end_date`
+/// (exclusive), over monthly periods beginning on the `start_date`.
+///
+/// The amount recognized in each period is rounded using the
+/// `rounding` policy, with the rounding residue recognized in the
+/// final period.
+///
+/// As a [TransactionGenerator](TransactionGenerator), a transaction is
+/// generated on the last day of each period, moving the recognized
+/// amount from the deferral account to the recognition account. The
+/// original payment is not included.
+///
+/// # Example
+/// ```
+/// use doublecount::{AccountID, Deferral, DeferralAccounts, DeferralKind, Proration};
+/// use commodity::Commodity;
+/// use chrono::NaiveDate;
+/// use std::str::FromStr;
+///
+/// let deferral = Deferral::new(
+///     Commodity::from_str("1200 USD").unwrap(),
+///     DeferralKind::PrepaidExpense,
+///     NaiveDate::from_ymd_opt(2020, 3, 15).unwrap(),
+///     NaiveDate::from_ymd_opt(2021, 3, 15).unwrap(),
+///     DeferralAccounts {
+///         deferral_account_id: AccountID::from("PrepaidInsurance").unwrap(),
+///         recognition_account_id: AccountID::from("Insurance").unwrap(),
+///     },
+/// )
+/// .with_proration(Proration::Months);
+///
+/// let table = deferral.recognition_table().unwrap();
+/// assert_eq!(12, table.len());
+/// assert_eq!(NaiveDate::from_ymd_opt(2020, 4, 14).unwrap(), table[0].end_date);
+/// assert_eq!(Commodity::from_str("100 USD").unwrap(), table[0].amount);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deferral {
+    /// The amount paid or received in advance.
+    pub amount: Commodity,
+    /// The type of amount being deferred.
+    pub kind: DeferralKind,
+    /// The first date covered by the amount.
+    pub start_date: NaiveDate,
+    /// The date after the last date covered by the amount.
+    pub end_date: NaiveDate,
+    /// How the amount is divided between periods.
+    pub proration: Proration,
+    /// The accounts involved in the deferral.
+    pub accounts: DeferralAccounts,
+    /// The policy used to round the amount recognized in each period.
+    pub rounding: RoundingPolicy,
+}
+
+impl Deferral {
+    /// Create a new [Deferral](Deferral), prorated by
+    /// [Days](Proration::Days) using the default
+    /// [RoundingPolicy](RoundingPolicy).
+    pub fn new(
+        amount: Commodity,
+        kind: DeferralKind,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+        accounts: DeferralAccounts,
+    ) -> Deferral {
+        Deferral {
+            amount,
+            kind,
+            start_date,
+            end_date,
+            proration: Proration::Days,
+            accounts,
+            rounding: RoundingPolicy::default(),
+        }
+    }
+
+    /// Set how the amount is divided between periods.
+    pub fn with_proration(mut self, proration: Proration) -> Deferral {
+        self.proration = proration;
+        self
+    }
+
+    /// Set the policy used to round the amount recognized in each
+    /// period.
+    pub fn with_rounding(mut self, rounding: RoundingPolicy) -> Deferral {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Calculate the recognition table, with a row for each period.
+    pub fn recognition_table(&self) -> Result<Vec<DeferralRow>, AccountingError> {
+        if self.end_date <= self.start_date {
+            return Err(AccountingError::InvalidDeferral(String::from(
+                "the end date must be after the start date",
+            )));
+        }
+
+        // (start, end (exclusive), weight) for each period
+        let mut periods: Vec<(NaiveDate, NaiveDate, Decimal)> = Vec::new();
+        let mut boundaries = Recurrence::Monthly.dates(self.start_date).peekable();
+
+        while let Some(period_start) = boundaries.next() {
+            if period_start >= self.end_date {
+                break;
+            }
+
+            let full_end = match boundaries.peek() {
+                Some(full_end) => *full_end,
+                None => break,
+            };
+            let period_end = full_end.min(self.end_date);
+            let days = Decimal::from((period_end - period_start).num_days());
+
+            let weight = match self.proration {
+                Proration::Days => days,
+                Proration::Months => days / Decimal::from((full_end - period_start).num_days()),
+            };
+
+            periods.push((period_start, period_end, weight));
+        }
+
+        let total_weight: Decimal = periods.iter().map(|(_, _, weight)| *weight).sum();
+        let type_id = self.amount.type_id;
+
+        let mut cumulative_weight = Decimal::ZERO;
+        let mut recognized = Decimal::ZERO;
+        let num_periods = periods.len();
+
+        Ok(periods
+            .into_iter()
+            .enumerate()
+            .map(|(i, (start_date, end_date, weight))| {
+                cumulative_weight += weight;
+
+                // calculated cumulatively so that the rounding residue
+                // lands in the final period
+                let target = if i + 1 == num_periods {
+                    self.amount.value
+                } else {
+                    self.rounding
+                        .round(self.amount.value * cumulative_weight / total_weight)
+                };
+                let amount = target - recognized;
+                recognized = target;
+
+                DeferralRow {
+                    period: i + 1,
+                    start_date,
+                    end_date: end_date - Duration::days(1),
+                    amount: Commodity::new(amount, type_id),
+                    remaining: Commodity::new(self.amount.value - recognized, type_id),
+                }
+            })
+            .collect())
+    }
+}
+
+impl TransactionGenerator for Deferral {
+    fn generate(&self) -> Result<Vec<Transaction>, AccountingError> {
+        let (from_account_id, to_account_id, description) = match self.kind {
+            DeferralKind::PrepaidExpense => (
+                self.accounts.deferral_account_id,
+                self.accounts.recognition_account_id,
+                "Prepaid expense recognized",
+            ),
+            DeferralKind::UnearnedRevenue => (
+                self.accounts.recognition_account_id,
+                self.accounts.deferral_account_id,
+                "Unearned revenue recognized",
+            ),
+        };
+
+        Ok(self
+            .recognition_table()?
+            .into_iter()
+            .filter(|row| !row.amount.value.is_zero())
+            .map(|row| {
+                Transaction::new(
+                    Some(format!(
+                        "{} from {} to {}",
+                        description, row.start_date, row.end_date
+                    )),
+                    row.end_date,
+                    vec![
+                        TransactionElement::new(from_account_id, Some(row.amount.neg()), None),
+                        TransactionElement::new(to_account_id, Some(row.amount), None),
+                    ],
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deferral, DeferralAccounts, DeferralKind, Proration};
+    use crate::{AccountID, AccountingError, TransactionGenerator};
+    use chrono::NaiveDate;
+    use commodity::Commodity;
+    use std::str::FromStr;
+
+    #[test]
+    fn deferral() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let accounts = DeferralAccounts {
+            deferral_account_id: AccountID::from("Unearned").unwrap(),
+            recognition_account_id: AccountID::from("Revenue").unwrap(),
+        };
+
+        let deferral = Deferral::new(
+            Commodity::from_str("100 USD").unwrap(),
+            DeferralKind::UnearnedRevenue,
+            date(2020, 1, 1),
+            date(2020, 3, 16),
+            accounts,
+        );
+
+        // 31, 29 and 15 days out of 75
+        let amounts: Vec<Commodity> = deferral
+            .recognition_table()
+            .unwrap()
+            .into_iter()
+            .map(|row| row.amount)
+            .collect();
+        assert_eq!(
+            vec![
+                Commodity::from_str("41.33 USD").unwrap(),
+                Commodity::from_str("38.67 USD").unwrap(),
+                Commodity::from_str("20.00 USD").unwrap(),
+            ],
+            amounts
+        );
+
+        // 1, 1, and 15/31 months
+        let table = deferral
+            .clone()
+            .with_proration(Proration::Months)
+            .recognition_table()
+            .unwrap();
+        assert_eq!(Commodity::from_str("40.26 USD").unwrap(), table[0].amount);
+        assert_eq!(Commodity::from_str("19.48 USD").unwrap(), table[2].amount);
+        assert_eq!(date(2020, 3, 15), table[2].end_date);
+        assert_eq!(Commodity::from_str("0 USD").unwrap(), table[2].remaining);
+
+        let transactions = deferral.generate().unwrap();
+        assert_eq!(date(2020, 1, 31), transactions[0].date);
+        assert_eq!(
+            Some(Commodity::from_str("41.33 USD").unwrap()),
+            transactions[0]
+                .get_element(&accounts.deferral_account_id)
+                .unwrap()
+                .amount
+        );
+    }
+
+    #[test]
+    fn deferral_single_period() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let accounts = DeferralAccounts {
+            deferral_account_id: AccountID::from("Prepaid").unwrap(),
+            recognition_account_id: AccountID::from("Expense").unwrap(),
+        };
+        let deferral = |start_date, end_date| {
+            Deferral::new(
+                Commodity::from_str("100 USD").unwrap(),
+                DeferralKind::PrepaidExpense,
+                start_date,
+                end_date,
+                accounts,
+            )
+        };
+
+        // a deferral covering no dates is rejected
+        for end_date in [date(2020, 1, 1), date(2019, 12, 31)] {
+            match deferral(date(2020, 1, 1), end_date).recognition_table() {
+                Err(AccountingError::InvalidDeferral(_)) => {}
+                _ => panic!("Expected an AccountingError::InvalidDeferral"),
+            }
+            assert!(deferral(date(2020, 1, 1), end_date).generate().is_err());
+        }
+
+        // a single day, and a single whole month, are recognized in a
+        // single period ending on the last date covered
+        for (end_date, proration) in [
+            (date(2020, 1, 2), Proration::Days),
+            (date(2020, 1, 2), Proration::Months),
+            (date(2020, 2, 1), Proration::Days),
+            (date(2020, 2, 1), Proration::Months),
+        ] {
+            let table = deferral(date(2020, 1, 1), end_date)
+                .with_proration(proration)
+                .recognition_table()
+                .unwrap();
+            assert_eq!(1, table.len());
+            assert_eq!(date(2020, 1, 1), table[0].start_date);
+            assert_eq!(end_date.pred_opt().unwrap(), table[0].end_date);
+            assert_eq!(Commodity::from_str("100 USD").unwrap(), table[0].amount);
+            assert_eq!(Commodity::from_str("0 USD").unwrap(), table[0].remaining);
+        }
+
+        let transactions = deferral(date(2020, 1, 1), date(2020, 1, 2))
+            .generate()
+            .unwrap();
+        assert_eq!(1, transactions.len());
+        assert_eq!(date(2020, 1, 1), transactions[0].date);
+        assert_eq!(
+            Some(Commodity::from_str("-100 USD").unwrap()),
+            transactions[0]
+                .get_element(&accounts.deferral_account_id)
+                .unwrap()
+                .amount
+        );
+    }
+}
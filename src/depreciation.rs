@@ -0,0 +1,426 @@
+use super::{
+    AccountID, AccountingError, Recurrence, RoundingPolicy, Transaction, TransactionElement,
+    TransactionGenerator,
+};
+use chrono::NaiveDate;
+use commodity::Commodity;
+use rust_decimal::Decimal;
+
+/// The method used to calculate the [Depreciation](Depreciation) of
+/// a fixed asset in each period.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DepreciationMethod {
+    /// The depreciable amount (cost less salvage value) is spread
+    /// evenly over the useful life.
+    StraightLine,
+    /// A fixed rate of `factor / useful_life` is applied to the book
+    /// value in each period (e.g. a `factor` of `2` for double
+    /// declining balance). The method switches to straight line
+    /// over the remaining life when that results in a larger
+    /// depreciation, so that the asset is depreciated to its
+    /// salvage value by the end of its useful life.
+    DecliningBalance { factor: Decimal },
+    /// The depreciable amount is spread in proportion to the number
+    /// of `units` produced in each period, out of the `total_units`
+    /// that the asset is expected to produce over its life.
+    UnitsOfProduction {
+        total_units: Decimal,
+        units: Vec<Decimal>,
+    },
+}
+
+/// The accounts involved in the [Depreciation](Depreciation) of a
+/// fixed asset.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DepreciationAccounts {
+    /// The account holding the cost of the asset. This is reduced by
+    /// the depreciation if there is no
+    /// `accumulated_depreciation_account_id`.
+    pub asset_account_id: AccountID,
+    /// The contra asset account which accumulates the depreciation.
+    pub accumulated_depreciation_account_id: Option<AccountID>,
+    /// The expense account which receives the depreciation.
+    pub expense_account_id: AccountID,
+}
+
+impl DepreciationAccounts {
+    /// The account which is reduced by the depreciation.
+    pub fn credit_account_id(&self) -> AccountID {
+        self.accumulated_depreciation_account_id
+            .unwrap_or(self.asset_account_id)
+    }
+}
+
+/// A row in the depreciation table of a
+/// [Depreciation](Depreciation), describing a single period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepreciationRow {
+    /// The number of the period, starting at `1`.
+    pub period: usize,
+    /// The date that the depreciation is recorded.
+    pub date: NaiveDate,
+    /// The depreciation for the period.
+    pub depreciation: Commodity,
+    /// The total depreciation at the end of the period.
+    pub accumulated_depreciation: Commodity,
+    /// The cost of the asset less the accumulated depreciation at the
+    /// end of the period.
+    pub book_value: Commodity,
+}
+
+/// The depreciation of a fixed asset from its `cost` to its
+/// `salvage_value` over its `useful_life` (a number of periods),
+/// recorded on the dates of a [Recurrence](Recurrence) beginning at
+/// the `first_date`.
+///
+/// If the asset was in service for only part of the first period,
+/// this can be specified using the `first_period_fraction` (e.g. `0.5`
+/// for the half-year convention), and the remainder of the
+/// depreciation is recorded in an additional final period.
+/// Amounts are rounded using the `rounding` policy, with the residue
+/// recorded in the final period.
+///
+/// As a [TransactionGenerator](TransactionGenerator), a transaction is
+/// generated for each period, from the accumulated depreciation (or
+/// asset) account to the depreciation expense account.
+///
+/// # Example
+/// ```
+/// use doublecount::{AccountID, Depreciation, DepreciationAccounts, DepreciationMethod, Recurrence};
+/// use commodity::Commodity;
+/// use chrono::NaiveDate;
+/// use rust_decimal::Decimal;
+/// use std::str::FromStr;
+///
+/// let depreciation = Depreciation::new(
+///     Commodity::from_str("10000 USD").unwrap(),
+///     Commodity::from_str("1000 USD").unwrap(),
+///     3,
+///     DepreciationMethod::StraightLine,
+///     NaiveDate::from_ymd_opt(2020, 12, 31).unwrap(),
+///     Recurrence::EveryNMonths(12),
+///     DepreciationAccounts {
+///         asset_account_id: AccountID::from("Vehicle").unwrap(),
+///         accumulated_depreciation_account_id: None,
+///         expense_account_id: AccountID::from("Depreciation").unwrap(),
+///     },
+/// )
+/// .with_first_period_fraction(Decimal::from_str("0.5").unwrap());
+///
+/// let table = depreciation.depreciation_table().unwrap();
+/// assert_eq!(4, table.len());
+/// assert_eq!(Commodity::from_str("1500 USD").unwrap(), table[0].depreciation);
+/// assert_eq!(Commodity::from_str("3000 USD").unwrap(), table[1].depreciation);
+/// assert_eq!(Commodity::from_str("1500 USD").unwrap(), table[3].depreciation);
+/// assert_eq!(Commodity::from_str("1000 USD").unwrap(), table[3].book_value);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Depreciation {
+    /// The cost of the asset.
+    pub cost: Commodity,
+    /// The value of the asset at the end of its useful life.
+    pub salvage_value: Commodity,
+    /// The number of periods that the asset is depreciated over.
+    pub useful_life: u32,
+    /// The method used to calculate the depreciation.
+    pub method: DepreciationMethod,
+    /// The date that the depreciation for the first period is
+    /// recorded.
+    pub first_date: NaiveDate,
+    /// The rule used to determine the dates of each period.
+    pub recurrence: Recurrence,
+    /// The accounts involved in the depreciation.
+    pub accounts: DepreciationAccounts,
+    /// The fraction of the first period that the asset was in
+    /// service for, between `0` (exclusive) and `1` (inclusive).
+    pub first_period_fraction: Decimal,
+    /// The policy used to round the depreciation for each period.
+    pub rounding: RoundingPolicy,
+}
+
+impl Depreciation {
+    /// Create a new [Depreciation](Depreciation), with the asset in
+    /// service for the entire first period, and the default
+    /// [RoundingPolicy](RoundingPolicy).
+    pub fn new(
+        cost: Commodity,
+        salvage_value: Commodity,
+        useful_life: u32,
+        method: DepreciationMethod,
+        first_date: NaiveDate,
+        recurrence: Recurrence,
+        accounts: DepreciationAccounts,
+    ) -> Depreciation {
+        Depreciation {
+            cost,
+            salvage_value,
+            useful_life,
+            method,
+            first_date,
+            recurrence,
+            accounts,
+            first_period_fraction: Decimal::new(1, 0),
+            rounding: RoundingPolicy::default(),
+        }
+    }
+
+    /// Set the fraction of the first period that the asset was in
+    /// service for.
+    pub fn with_first_period_fraction(mut self, first_period_fraction: Decimal) -> Depreciation {
+        self.first_period_fraction = first_period_fraction;
+        self
+    }
+
+    /// Set the policy used to round the depreciation for each period.
+    pub fn with_rounding(mut self, rounding: RoundingPolicy) -> Depreciation {
+        self.rounding = rounding;
+        self
+    }
+
+    /// The number of periods that depreciation is recorded for.
+    pub fn num_periods(&self) -> usize {
+        match &self.method {
+            DepreciationMethod::UnitsOfProduction { units, .. } => units.len(),
+            _ => {
+                let partial = self.first_period_fraction < Decimal::new(1, 0);
+                self.useful_life as usize + partial as usize
+            }
+        }
+    }
+
+    /// The fraction of a full period for the period with the given
+    /// (1 based) number.
+    fn period_fraction(&self, period: usize) -> Decimal {
+        if period == 1 {
+            self.first_period_fraction
+        } else if period > self.useful_life as usize {
+            Decimal::new(1, 0) - self.first_period_fraction
+        } else {
+            Decimal::new(1, 0)
+        }
+    }
+
+    /// Whether the depreciation is based on time (and therefore
+    /// fully depreciated in the final period).
+    fn is_time_based(&self) -> bool {
+        !matches!(self.method, DepreciationMethod::UnitsOfProduction { .. })
+    }
+
+    fn validate(&self) -> Result<(), AccountingError> {
+        let invalid = |reason: &str| Err(AccountingError::InvalidDepreciation(reason.to_string()));
+
+        if self.salvage_value.type_id != self.cost.type_id {
+            return invalid("the salvage value is not in the same commodity as the cost");
+        }
+        if self.salvage_value.value > self.cost.value {
+            return invalid("the salvage value is greater than the cost");
+        }
+        if self.useful_life == 0 {
+            return invalid("the useful life must be at least one period");
+        }
+        if self.first_period_fraction <= Decimal::ZERO
+            || self.first_period_fraction > Decimal::new(1, 0)
+        {
+            return invalid("the first period fraction must be greater than 0 and at most 1");
+        }
+        if let DepreciationMethod::UnitsOfProduction { total_units, .. } = &self.method {
+            if *total_units <= Decimal::ZERO {
+                return invalid("the total units must be greater than 0");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calculate the depreciation table, with a row for each period.
+    pub fn depreciation_table(&self) -> Result<Vec<DepreciationRow>, AccountingError> {
+        self.validate()?;
+
+        let type_id = self.cost.type_id;
+        let depreciable = self.cost.value - self.salvage_value.value;
+        let life = Decimal::from(self.useful_life);
+        let num_periods = self.num_periods();
+
+        let mut accumulated = Decimal::ZERO;
+        let mut elapsed = Decimal::ZERO;
+        let mut units_produced = Decimal::ZERO;
+        let mut table = Vec::with_capacity(num_periods);
+
+        for (i, date) in self
+            .recurrence
+            .dates(self.first_date)
+            .take(num_periods)
+            .enumerate()
+        {
+            let period = i + 1;
+            let fraction = self.period_fraction(period);
+            let remaining = depreciable - accumulated;
+
+            let depreciation = match &self.method {
+                _ if period == num_periods && self.is_time_based() => remaining,
+                DepreciationMethod::StraightLine => {
+                    self.rounding.round(depreciable / life * fraction)
+                }
+                DepreciationMethod::DecliningBalance { factor } => {
+                    let book_value = self.cost.value - accumulated;
+                    let declining = book_value * *factor / life * fraction;
+                    let straight_line = remaining / (life - elapsed) * fraction;
+                    self.rounding.round(declining.max(straight_line))
+                }
+                DepreciationMethod::UnitsOfProduction { total_units, units } => {
+                    // calculated cumulatively so that rounding residue
+                    // does not accumulate
+                    units_produced += units[i];
+                    let target = depreciable * units_produced.min(*total_units) / *total_units;
+                    self.rounding.round(target) - accumulated
+                }
+            }
+            .min(remaining);
+
+            accumulated += depreciation;
+            elapsed += fraction;
+
+            table.push(DepreciationRow {
+                period,
+                date,
+                depreciation: Commodity::new(depreciation, type_id),
+                accumulated_depreciation: Commodity::new(accumulated, type_id),
+                book_value: Commodity::new(self.cost.value - accumulated, type_id),
+            });
+        }
+
+        Ok(table)
+    }
+}
+
+impl TransactionGenerator for Depreciation {
+    fn generate(&self) -> Result<Vec<Transaction>, AccountingError> {
+        Ok(self
+            .depreciation_table()?
+            .into_iter()
+            .filter(|row| !row.depreciation.value.is_zero())
+            .map(|row| {
+                Transaction::new(
+                    Some(format!("Depreciation for period {}", row.period)),
+                    row.date,
+                    vec![
+                        TransactionElement::new(
+                            self.accounts.credit_account_id(),
+                            Some(row.depreciation.neg()),
+                            None,
+                        ),
+                        TransactionElement::new(
+                            self.accounts.expense_account_id,
+                            Some(row.depreciation),
+                            None,
+                        ),
+                    ],
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Depreciation, DepreciationAccounts, DepreciationMethod};
+    use crate::{AccountID, Recurrence, TransactionGenerator};
+    use chrono::NaiveDate;
+    use commodity::Commodity;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn depreciation(method: DepreciationMethod) -> Depreciation {
+        Depreciation::new(
+            Commodity::from_str("10000 USD").unwrap(),
+            Commodity::from_str("1000 USD").unwrap(),
+            5,
+            method,
+            NaiveDate::from_ymd_opt(2020, 12, 31).unwrap(),
+            Recurrence::EveryNMonths(12),
+            DepreciationAccounts {
+                asset_account_id: AccountID::from("Equipment").unwrap(),
+                accumulated_depreciation_account_id: Some(
+                    AccountID::from("AccDepreciation").unwrap(),
+                ),
+                expense_account_id: AccountID::from("Depreciation").unwrap(),
+            },
+        )
+    }
+
+    fn depreciations(depreciation: &Depreciation) -> Vec<Commodity> {
+        depreciation
+            .depreciation_table()
+            .unwrap()
+            .into_iter()
+            .map(|row| row.depreciation)
+            .collect()
+    }
+
+    #[test]
+    fn declining_balance() {
+        let depreciation = depreciation(DepreciationMethod::DecliningBalance {
+            factor: Decimal::new(2, 0),
+        });
+
+        let expected: Vec<Commodity> = ["4000", "2400", "1440", "864", "296"]
+            .iter()
+            .map(|value| Commodity::from_str(&format!("{} USD", value)).unwrap())
+            .collect();
+        assert_eq!(expected, depreciations(&depreciation));
+
+        let transactions = depreciation.generate().unwrap();
+        assert_eq!(5, transactions.len());
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 12, 31).unwrap(),
+            transactions[4].date
+        );
+        assert_eq!(
+            Some(Commodity::from_str("-296 USD").unwrap()),
+            transactions[4]
+                .get_element(&AccountID::from("AccDepreciation").unwrap())
+                .unwrap()
+                .amount
+        );
+    }
+
+    #[test]
+    fn units_of_production() {
+        let depreciation = depreciation(DepreciationMethod::UnitsOfProduction {
+            total_units: Decimal::new(3000, 0),
+            units: vec![
+                Decimal::new(1000, 0),
+                Decimal::new(1000, 0),
+                Decimal::new(500, 0),
+            ],
+        });
+
+        let expected: Vec<Commodity> = ["3000", "3000", "1500"]
+            .iter()
+            .map(|value| Commodity::from_str(&format!("{} USD", value)).unwrap())
+            .collect();
+        assert_eq!(expected, depreciations(&depreciation));
+
+        let depreciation = depreciation.with_first_period_fraction(Decimal::ZERO);
+        assert!(depreciation.depreciation_table().is_err());
+    }
+
+    #[test]
+    fn straight_line_rounding() {
+        let depreciation = Depreciation::new(
+            Commodity::from_str("1000 USD").unwrap(),
+            Commodity::from_str("0 USD").unwrap(),
+            3,
+            DepreciationMethod::StraightLine,
+            NaiveDate::from_ymd_opt(2020, 1, 31).unwrap(),
+            Recurrence::Monthly,
+            depreciation(DepreciationMethod::StraightLine).accounts,
+        );
+
+        let expected: Vec<Commodity> = ["333.33", "333.33", "333.34"]
+            .iter()
+            .map(|value| Commodity::from_str(&format!("{} USD", value)).unwrap())
+            .collect();
+        assert_eq!(expected, depreciations(&depreciation));
+    }
+}
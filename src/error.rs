@@ -1,4 +1,8 @@
-use super::{AccountID, AccountStatus, FailedBalanceAssertion, Transaction};
+use super::{
+    AccountID, AccountStatus, CheckSumFailure, ConstraintViolation, FailedBalanceAssertion,
+    PostingDirection, Transaction,
+};
+use chrono::NaiveDate;
 use commodity::exchange_rate::ExchangeRateError;
 use commodity::{Commodity, CommodityError, CommodityTypeID};
 use thiserror::Error;
@@ -12,21 +16,51 @@ pub enum AccountingError {
     Commodity(#[from] CommodityError),
     #[error("error relating to exchange rates")]
     ExchangeRate(#[from] ExchangeRateError),
-    #[error("invalid account status ({:?}) for account {}", .status, .account_id)]
+    #[error("invalid account status ({:?}) for account {} to post an {} amount", .status, .account_id, .direction)]
     InvalidAccountStatus {
         account_id: AccountID,
         status: AccountStatus,
+        direction: PostingDirection,
+    },
+    #[error("cannot set the status of account {account_id} to {status:?} on {date} because it is inconsistent with the account's declared open and close dates")]
+    AccountStatusDateMismatch {
+        account_id: AccountID,
+        status: AccountStatus,
+        date: NaiveDate,
+    },
+    #[error("cannot close account {account_id} because it has a non-zero balance of {balance}")]
+    NonZeroAccountClose {
+        account_id: AccountID,
+        balance: Commodity,
     },
     #[error("error parsing a date from string")]
     DateParseError(#[from] chrono::ParseError),
     #[error("invalid transaction {0:?} because {1}")]
-    InvalidTransaction(Transaction, String),
-    #[error("failed checksum, the sum of account values in the common commodity type ({0}) does not equal zero")]
-    FailedCheckSum(Commodity),
+    InvalidTransaction(Box<Transaction>, String),
+    #[error("failed checksum, {0}")]
+    FailedCheckSum(Box<CheckSumFailure>),
     #[error("no exchange rate supplied, unable to convert commodity {0} to type {1}")]
     NoExchangeRateSupplied(Commodity, CommodityTypeID),
     #[error("the account state with the id {0} was requested but cannot be found")]
     MissingAccountState(AccountID),
     #[error("the balance assertion failed {0}")]
     BalanceAssertionFailed(FailedBalanceAssertion),
+    #[error("there is no balance assertion following the pad for account {0}")]
+    UnusedPad(AccountID),
+    #[error("no price available on {2}, unable to convert commodity {0} to type {1}")]
+    NoPriceAvailable(Commodity, CommodityTypeID, NaiveDate),
+    #[error("no path of exchange rates available to convert commodity {0} to type {1}")]
+    NoConversionPath(Commodity, CommodityTypeID),
+    #[error("the schedule has no end date or count, so it would generate an infinite number of transactions")]
+    UnboundedSchedule,
+    #[error("invalid loan because {0}")]
+    InvalidLoan(String),
+    #[error("invalid depreciation because {0}")]
+    InvalidDepreciation(String),
+    #[error("invalid deferral because {0}")]
+    InvalidDeferral(String),
+    #[error("the action at index {0} is not in order with the action before it")]
+    UnsortedActions(usize),
+    #[error("an account constraint was violated because {0}")]
+    AccountConstraintViolated(Box<ConstraintViolation>),
 }
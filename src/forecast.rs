@@ -0,0 +1,341 @@
+use super::{AccountID, AccountState};
+use chrono::NaiveDate;
+use commodity::{Commodity, CommodityTypeID};
+use std::collections::{BTreeMap, HashMap};
+
+/// Whether a balance in a [Forecast](Forecast) is the result of
+/// actions which have actually occurred, or of projected actions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BalanceKind {
+    /// The balance is the result of actions on or before the
+    /// forecast's `today` date.
+    Actual,
+    /// The balance is the result of actions after the forecast's
+    /// `today` date.
+    Projected,
+}
+
+/// The balance of an [Account](crate::Account) at the end of a given
+/// date, within a [Forecast](Forecast).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalancePoint {
+    /// The date of the balance.
+    pub date: NaiveDate,
+    /// The balance of the account at the end of the date.
+    pub balance: Commodity,
+    /// Whether this balance is actual or projected.
+    pub kind: BalanceKind,
+}
+
+/// The history of account balances produced by
+/// [ProgramState::forecast()](crate::ProgramState::forecast()),
+/// distinguishing between actual and projected balances.
+///
+/// A [BalancePoint](BalancePoint) is recorded for each commodity type
+/// held by an account on each date that its balance changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    /// The last date which is considered to be actual rather than
+    /// projected.
+    pub today: NaiveDate,
+    /// The last date included in the forecast.
+    pub horizon: NaiveDate,
+    history: HashMap<(AccountID, CommodityTypeID), Vec<BalancePoint>>,
+    commodity_type_ids: HashMap<AccountID, CommodityTypeID>,
+}
+
+impl Forecast {
+    /// Create a new empty [Forecast](Forecast).
+    pub fn new(today: NaiveDate, horizon: NaiveDate) -> Forecast {
+        Forecast {
+            today,
+            horizon,
+            history: HashMap::new(),
+            commodity_type_ids: HashMap::new(),
+        }
+    }
+
+    /// The kind of balance on the given `date`.
+    pub fn kind_on(&self, date: NaiveDate) -> BalanceKind {
+        if date <= self.today {
+            BalanceKind::Actual
+        } else {
+            BalanceKind::Projected
+        }
+    }
+
+    /// Create a [Forecast](Forecast) from the history of balances
+    /// recorded by a [ProgramState](crate::ProgramState), which
+    /// contains the balance of each commodity type held by each
+    /// account at the end of each date that it changed.
+    pub(crate) fn from_balance_history(
+        today: NaiveDate,
+        horizon: NaiveDate,
+        balance_history: &HashMap<(AccountID, CommodityTypeID), BTreeMap<NaiveDate, Commodity>>,
+        account_states: &HashMap<AccountID, AccountState>,
+    ) -> Forecast {
+        let mut forecast = Forecast::new(today, horizon);
+
+        for (account_id, account_state) in account_states {
+            forecast
+                .commodity_type_ids
+                .insert(*account_id, account_state.account.commodity_type_id);
+        }
+
+        for (key, balances) in balance_history {
+            let history: Vec<BalancePoint> = balances
+                .range(..=horizon)
+                .map(|(date, balance)| BalancePoint {
+                    date: *date,
+                    balance: *balance,
+                    kind: forecast.kind_on(*date),
+                })
+                .collect();
+            forecast.history.insert(*key, history);
+        }
+
+        forecast
+    }
+
+    /// The history of balances of the account's own
+    /// `commodity_type_id` for the account with the given id, ordered
+    /// by date.
+    pub fn history(&self, account_id: &AccountID) -> &[BalancePoint] {
+        match self.commodity_type_ids.get(account_id) {
+            Some(type_id) => self.holding_history(account_id, *type_id),
+            None => &[],
+        }
+    }
+
+    /// The history of balances of the commodity type with the id
+    /// `type_id` held by the account with the given id, ordered by
+    /// date. This includes the commodities held in the inventory of
+    /// an account which tracks lots.
+    pub fn holding_history(
+        &self,
+        account_id: &AccountID,
+        type_id: CommodityTypeID,
+    ) -> &[BalancePoint] {
+        self.history
+            .get(&(*account_id, type_id))
+            .map(|history| history.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// The balance of the account with the given id at the end of
+    /// the given `date`. Returns `None` if the account's balance has
+    /// not changed from zero by this date.
+    pub fn balance_on(&self, account_id: &AccountID, date: NaiveDate) -> Option<&BalancePoint> {
+        self.history(account_id)
+            .iter()
+            .take_while(|point| point.date <= date)
+            .last()
+    }
+
+    /// The first projected balance for the account with the given id
+    /// which is negative, if any. This answers the question "will
+    /// this account go negative before the horizon?".
+    pub fn first_projected_negative(&self, account_id: &AccountID) -> Option<&BalancePoint> {
+        self.history(account_id).iter().find(|point| {
+            point.kind == BalanceKind::Projected && point.balance.value.is_sign_negative()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BalanceKind;
+    use crate::{
+        Account, AccountStatus, ActionTypeValue, Program, ProgramState, Recurrence, Schedule,
+        Transaction, TransactionElement,
+    };
+    use chrono::NaiveDate;
+    use commodity::{Commodity, CommodityTypeID};
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    #[test]
+    fn forecast() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), usd, None));
+        let income = Rc::from(Account::new_with_id(Some("Income"), usd, None));
+        let rent = Rc::from(Account::new_with_id(Some("Rent"), usd, None));
+
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(
+                Transaction::new_simple(
+                    Some("Salary"),
+                    date(2020, 1, 1),
+                    income.id,
+                    bank.id,
+                    Commodity::from_str("1500 USD").unwrap(),
+                    None,
+                )
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new_simple(
+                    Some("Bonus"),
+                    date(2020, 3, 20),
+                    income.id,
+                    bank.id,
+                    Commodity::from_str("500 USD").unwrap(),
+                    None,
+                )
+                .into(),
+            ),
+        ];
+        let program = Program::new(actions);
+
+        let rent_schedule = Schedule::new(
+            Transaction::new_simple(
+                Some("Rent"),
+                date(2020, 1, 15),
+                bank.id,
+                rent.id,
+                Commodity::from_str("1000 USD").unwrap(),
+                None,
+            ),
+            Recurrence::Monthly,
+        );
+
+        let program_state: ProgramState =
+            ProgramState::new(&[bank.clone(), income, rent], AccountStatus::Open);
+        let forecast = program_state
+            .forecast(
+                &program,
+                &[rent_schedule],
+                date(2020, 1, 20),
+                date(2020, 3, 31),
+            )
+            .unwrap();
+
+        // scheduled transactions on or before today are expected to
+        // have already been entered into the program, so the rent
+        // on the 15th of January is not projected.
+        let history = forecast.history(&bank.id);
+        assert_eq!(4, history.len());
+        assert_eq!(BalanceKind::Actual, history[0].kind);
+        assert_eq!(
+            Commodity::from_str("1500 USD").unwrap(),
+            forecast
+                .balance_on(&bank.id, date(2020, 1, 31))
+                .unwrap()
+                .balance
+        );
+
+        let negative = forecast.first_projected_negative(&bank.id).unwrap();
+        assert_eq!(date(2020, 3, 15), negative.date);
+        assert_eq!(Commodity::from_str("-500 USD").unwrap(), negative.balance);
+        assert_eq!(
+            Commodity::from_str("0 USD").unwrap(),
+            forecast
+                .balance_on(&bank.id, date(2020, 3, 31))
+                .unwrap()
+                .balance
+        );
+
+        // the projected transactions are not performed on the state
+        assert_eq!(
+            Commodity::from_str("0 USD").unwrap(),
+            program_state.get_account_state(&bank.id).unwrap().amount
+        );
+        assert!(program_state.journal.is_empty());
+        assert!(!program_state.record_balance_history);
+    }
+
+    #[test]
+    fn forecast_inventory() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), usd, None));
+        let broker = Rc::from(Account::new_with_id(Some("Broker"), usd, None).with_inventory());
+        let custodian =
+            Rc::from(Account::new_with_id(Some("Custodian"), usd, None).with_inventory());
+
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let element = |account: &Account, shares: &str, cost: &str| {
+            TransactionElement::new(account.id, Some(Commodity::from_str(shares).unwrap()), None)
+                .with_cost(Commodity::from_str(cost).unwrap())
+        };
+        let buy = |date: NaiveDate, shares: &str, cost: &str| {
+            Transaction::new(
+                Some("Buy"),
+                date,
+                vec![
+                    element(&broker, shares, cost),
+                    TransactionElement::new(bank.id, None, None),
+                ],
+            )
+        };
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(buy(date(2020, 1, 1), "10 AAPL", "150 USD").into()),
+            Rc::new(
+                Transaction::new(
+                    Some("Transfer"),
+                    date(2020, 1, 25),
+                    vec![
+                        element(&broker, "-10 AAPL", "150 USD"),
+                        element(&custodian, "10 AAPL", "150 USD"),
+                    ],
+                )
+                .into(),
+            ),
+        ];
+        let program = Program::new(actions);
+        let buy_schedule = Schedule::new(
+            buy(date(2020, 2, 1), "5 AAPL", "100 USD"),
+            Recurrence::Monthly,
+        );
+
+        let program_state: ProgramState = ProgramState::new(
+            &[bank.clone(), broker.clone(), custodian.clone()],
+            AccountStatus::Open,
+        );
+        let forecast = program_state
+            .forecast(
+                &program,
+                &[buy_schedule],
+                date(2020, 1, 20),
+                date(2020, 3, 31),
+            )
+            .unwrap();
+
+        // the commodities held in the inventory are recorded, including
+        // when the account no longer holds them
+        let balances = |account: &Account| -> Vec<(NaiveDate, Commodity)> {
+            forecast
+                .holding_history(&account.id, aapl)
+                .iter()
+                .map(|point| (point.date, point.balance))
+                .collect()
+        };
+        assert_eq!(
+            vec![
+                (date(2020, 1, 1), Commodity::from_str("10 AAPL").unwrap()),
+                (date(2020, 1, 25), Commodity::from_str("0 AAPL").unwrap()),
+                (date(2020, 2, 1), Commodity::from_str("5 AAPL").unwrap()),
+                (date(2020, 3, 1), Commodity::from_str("10 AAPL").unwrap()),
+            ],
+            balances(&broker)
+        );
+        assert_eq!(
+            vec![(date(2020, 1, 25), Commodity::from_str("10 AAPL").unwrap())],
+            balances(&custodian)
+        );
+
+        // the history of an account is the history of its own
+        // commodity type
+        assert!(forecast.history(&broker.id).is_empty());
+        assert_eq!(
+            Commodity::from_str("-2500 USD").unwrap(),
+            forecast
+                .balance_on(&bank.id, date(2020, 3, 31))
+                .unwrap()
+                .balance
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use super::{sum_account_states, AccountID, AccountState, AccountingError};
+use chrono::NaiveDate;
+use commodity::exchange_rate::ExchangeRate;
+use commodity::{Commodity, CommodityTypeID};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt;
+
+/// How the sum of all account balances is checked by an
+/// [IntegrityMode](IntegrityMode).
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegrityCheck {
+    /// The balances of the accounts holding each commodity type need
+    /// to sum to zero. Transactions which convert between commodity
+    /// types (such as trades) will fail this check, use
+    /// [Converted](IntegrityCheck::Converted) for programs containing
+    /// them.
+    PerCommodity,
+    /// The balances of all the accounts, converted to the commodity
+    /// type with the id `commodity_type_id` using the
+    /// `exchange_rate`, need to sum to zero.
+    Converted {
+        commodity_type_id: CommodityTypeID,
+        exchange_rate: ExchangeRate,
+    },
+}
+
+/// An optional mode for a [ProgramState](crate::ProgramState) to
+/// verify the double entry invariant (that the sum of all account
+/// balances is zero) while a [Program](crate::Program) is being
+/// executed, raising a
+/// [FailedCheckSum](AccountingError::FailedCheckSum) error when it
+/// does not hold.
+///
+/// This is useful to catch custom actions which do not perform
+/// balanced transactions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityMode {
+    /// How the sum of all account balances is checked.
+    pub check: IntegrityCheck,
+    /// The number of actions performed between each check. The
+    /// check is always performed after the last action.
+    pub interval: usize,
+    /// The tolerance allowed for the sum to differ from zero.
+    pub tolerance: Decimal,
+}
+
+impl IntegrityMode {
+    /// Create a new [IntegrityMode](IntegrityMode) which performs the
+    /// `check` after every action, with no tolerance.
+    pub fn new(check: IntegrityCheck) -> IntegrityMode {
+        IntegrityMode {
+            check,
+            interval: 1,
+            tolerance: Decimal::ZERO,
+        }
+    }
+
+    /// Set the number of actions performed between each check. An
+    /// `interval` of `0` is treated as `1`.
+    pub fn with_interval(mut self, interval: usize) -> IntegrityMode {
+        self.interval = interval.max(1);
+        self
+    }
+
+    /// Set the `tolerance` allowed for the sum to differ from zero.
+    pub fn with_tolerance(mut self, tolerance: Decimal) -> IntegrityMode {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Returns true if the check needs to be performed after the
+    /// action at `index`, of a total of `num_actions`.
+    pub fn is_due(&self, index: usize, num_actions: usize) -> bool {
+        (index + 1) % self.interval.max(1) == 0 || index + 1 == num_actions
+    }
+
+    /// Check the sum of the `account_states`, returning the sum which
+    /// does not equal zero if the check fails.
+    pub fn unbalanced_sum(
+        &self,
+        account_states: &HashMap<AccountID, AccountState>,
+    ) -> Result<Option<Commodity>, AccountingError> {
+        let sums: Vec<Commodity> = match &self.check {
+            IntegrityCheck::PerCommodity => {
+                let mut sums: HashMap<CommodityTypeID, Commodity> = HashMap::new();
+                for amount in account_states.values().flat_map(AccountState::balances) {
+                    let sum = sums
+                        .entry(amount.type_id)
+                        .or_insert_with(|| Commodity::zero(amount.type_id));
+                    *sum = sum.add(&amount)?;
+                }
+
+                let mut sums: Vec<Commodity> = sums.into_values().collect();
+                // so that the same failure is reported each time
+                sums.sort_by_key(|sum| sum.type_id.to_string());
+                sums
+            }
+            IntegrityCheck::Converted {
+                commodity_type_id,
+                exchange_rate,
+            } => vec![sum_account_states(
+                account_states,
+                *commodity_type_id,
+                Some(exchange_rate),
+            )?],
+        };
+
+        Ok(sums
+            .into_iter()
+            .find(|sum| sum.value.abs() > self.tolerance))
+    }
+}
+
+/// The details of a failed check by an
+/// [IntegrityMode](IntegrityMode), provided by the
+/// [FailedCheckSum](AccountingError::FailedCheckSum) error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckSumFailure {
+    /// The sum of the account balances, which does not equal zero.
+    pub sum: Commodity,
+    /// The index of the action within the [Program](crate::Program)
+    /// after which the check failed.
+    pub action_index: usize,
+    /// The date of the action after which the check failed.
+    pub action_date: NaiveDate,
+    /// A description of the action after which the check failed.
+    pub action: String,
+}
+
+impl fmt::Display for CheckSumFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the sum of account values in the common commodity type ({}) does not equal zero after the action at index {} on {} ({})",
+            self.sum, self.action_index, self.action_date, self.action
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IntegrityCheck, IntegrityMode};
+    use crate::{
+        Account, AccountID, AccountStatus, AccountingError, Action, ActionTypeValueEnum,
+        ProgramState, Transaction,
+    };
+    use chrono::NaiveDate;
+    use commodity::{Commodity, CommodityType};
+    use std::fmt;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    /// An action which breaks double entry, by depositing into an
+    /// account without a balancing element.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Deposit {
+        account_id: AccountID,
+        date: NaiveDate,
+        amount: Commodity,
+    }
+
+    impl fmt::Display for Deposit {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Deposit")
+        }
+    }
+
+    impl<AT, ATV> Action<AT, ATV> for Deposit
+    where
+        ATV: ActionTypeValueEnum<AT>,
+    {
+        fn date(&self) -> NaiveDate {
+            self.date
+        }
+
+        fn perform(
+            &self,
+            program_state: &mut ProgramState<AT, ATV>,
+        ) -> Result<(), AccountingError> {
+            let state = program_state
+                .get_account_state_mut(&self.account_id)
+                .ok_or(AccountingError::MissingAccountState(self.account_id))?;
+            state.amount = state.amount.add(&self.amount)?;
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum TestActionTypeValue {
+        Transaction(Transaction),
+        Deposit(Deposit),
+    }
+
+    impl ActionTypeValueEnum<()> for TestActionTypeValue {
+        fn as_action(&self) -> &dyn Action<(), Self> {
+            match self {
+                TestActionTypeValue::Transaction(action) => action,
+                TestActionTypeValue::Deposit(action) => action,
+            }
+        }
+    }
+
+    impl crate::ActionTypeFor<()> for TestActionTypeValue {
+        fn action_type(&self) {}
+    }
+
+    #[test]
+    fn integrity_mode() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let account1 = Rc::from(Account::new_with_id(Some("Account 1"), aud.id, None));
+        let account2 = Rc::from(Account::new_with_id(Some("Account 2"), aud.id, None));
+        let accounts = vec![account1.clone(), account2.clone()];
+
+        let actions = vec![
+            Rc::new(TestActionTypeValue::Transaction(Transaction::new_simple::<
+                String,
+            >(
+                None,
+                date,
+                account1.id,
+                account2.id,
+                Commodity::from_str("10 AUD").unwrap(),
+                None,
+            ))),
+            Rc::new(TestActionTypeValue::Deposit(Deposit {
+                account_id: account1.id,
+                date,
+                amount: Commodity::from_str("5 AUD").unwrap(),
+            })),
+            Rc::new(TestActionTypeValue::Transaction(Transaction::new_simple::<
+                String,
+            >(
+                None,
+                date,
+                account2.id,
+                account1.id,
+                Commodity::from_str("3 AUD").unwrap(),
+                None,
+            ))),
+        ];
+        let program = crate::Program::new(actions);
+
+        // without an integrity mode, the program executes
+        let mut program_state: ProgramState<(), TestActionTypeValue> =
+            ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.execute_program(&program).unwrap();
+
+        let mut program_state: ProgramState<(), TestActionTypeValue> =
+            ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.integrity_mode = Some(IntegrityMode::new(IntegrityCheck::PerCommodity));
+        match program_state.execute_program(&program) {
+            Err(AccountingError::FailedCheckSum(failure)) => {
+                assert_eq!(Commodity::from_str("5 AUD").unwrap(), failure.sum);
+                assert_eq!(1, failure.action_index);
+                assert!(failure.action.contains("Deposit"));
+            }
+            _ => panic!("Expected an AccountingError::FailedCheckSum"),
+        }
+
+        // only checked at the end of the program
+        let mut program_state: ProgramState<(), TestActionTypeValue> =
+            ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.integrity_mode =
+            Some(IntegrityMode::new(IntegrityCheck::PerCommodity).with_interval(10));
+        match program_state.execute_program(&program) {
+            Err(AccountingError::FailedCheckSum(failure)) => assert_eq!(2, failure.action_index),
+            _ => panic!("Expected an AccountingError::FailedCheckSum"),
+        }
+
+        // an interval of 0 checks after every action
+        let mut program_state: ProgramState<(), TestActionTypeValue> =
+            ProgramState::new(&accounts, AccountStatus::Open);
+        program_state.integrity_mode =
+            Some(IntegrityMode::new(IntegrityCheck::PerCommodity).with_interval(0));
+        match program_state.execute_program(&program) {
+            Err(AccountingError::FailedCheckSum(failure)) => assert_eq!(1, failure.action_index),
+            _ => panic!("Expected an AccountingError::FailedCheckSum"),
+        }
+    }
+
+    #[test]
+    fn is_due() {
+        let mode = IntegrityMode::new(IntegrityCheck::PerCommodity);
+        assert_eq!(1, mode.clone().with_interval(0).interval);
+        assert!((0..3).all(|index| mode.is_due(index, 3)));
+
+        // an interval of 0 set directly is also treated as 1
+        let zero = IntegrityMode {
+            interval: 0,
+            ..mode.clone()
+        };
+        assert!((0..3).all(|index| zero.is_due(index, 3)));
+
+        // due after every third action, and after the last action
+        let every_third = mode.with_interval(3);
+        let due: Vec<usize> = (0..7)
+            .filter(|index| every_third.is_due(*index, 7))
+            .collect();
+        assert_eq!(vec![2, 5, 6], due);
+        // when the last action falls on the interval, and when it is
+        // the only action
+        let due: Vec<usize> = (0..6)
+            .filter(|index| every_third.is_due(*index, 6))
+            .collect();
+        assert_eq!(vec![2, 5], due);
+        assert!(every_third.is_due(0, 1));
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+
+#[cfg(feature = "serde-support")]
+use serde::{Deserialize, Serialize};
+
+/// The convention used to calculate the fraction of a year between
+/// two dates, when calculating interest.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DayCount {
+    /// The actual number of days, divided by 365 (ACT/365 Fixed).
+    Actual365,
+    /// The actual number of days, divided by 360 (ACT/360).
+    Actual360,
+    /// Each month is considered to have 30 days, and each year 360
+    /// days (30/360 US bond basis).
+    Thirty360,
+}
+
+impl DayCount {
+    /// The number of days between `start` and `end` according to
+    /// this convention.
+    pub fn days(&self, start: NaiveDate, end: NaiveDate) -> i64 {
+        match self {
+            DayCount::Actual365 | DayCount::Actual360 => (end - start).num_days(),
+            DayCount::Thirty360 => {
+                let mut start_day = start.day() as i64;
+                let mut end_day = end.day() as i64;
+
+                if start_day == 31 {
+                    start_day = 30;
+                }
+                if end_day == 31 && start_day == 30 {
+                    end_day = 30;
+                }
+
+                360 * (end.year() as i64 - start.year() as i64)
+                    + 30 * (end.month() as i64 - start.month() as i64)
+                    + (end_day - start_day)
+            }
+        }
+    }
+
+    /// The number of days in a year according to this convention.
+    pub fn days_in_year(&self) -> i64 {
+        match self {
+            DayCount::Actual365 => 365,
+            DayCount::Actual360 | DayCount::Thirty360 => 360,
+        }
+    }
+
+    /// The fraction of a year between `start` and `end` according to
+    /// this convention.
+    pub fn year_fraction(&self, start: NaiveDate, end: NaiveDate) -> Decimal {
+        Decimal::from(self.days(start, end)) / Decimal::from(self.days_in_year())
+    }
+}
+
+/// The method used to calculate interest over a period.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InterestMethod {
+    /// Interest is calculated on the balance only.
+    Simple,
+    /// Interest is compounded daily, calculated on the balance plus
+    /// the interest accrued so far within the period.
+    Compound,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DayCount;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn day_count() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(
+            29,
+            DayCount::Actual365.days(date(2020, 2, 1), date(2020, 3, 1))
+        );
+        assert_eq!(
+            30,
+            DayCount::Thirty360.days(date(2020, 2, 1), date(2020, 3, 1))
+        );
+        assert_eq!(
+            31,
+            DayCount::Thirty360.days(date(2020, 1, 31), date(2020, 3, 1))
+        );
+        assert_eq!(
+            0,
+            DayCount::Thirty360.days(date(2020, 1, 30), date(2020, 1, 31))
+        );
+        assert_eq!(
+            Decimal::new(1, 0),
+            DayCount::Thirty360.year_fraction(date(2020, 1, 1), date(2021, 1, 1))
+        );
+    }
+}
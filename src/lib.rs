@@ -7,6 +7,11 @@
 //! + `serde-support`
 //!   + Disabled by default
 //!   + Enables support for serialization/de-serialization via `serde`
+//! + `derive`
+//!   + Disabled by default
+//!   + Enables the `ActionTypeValueEnum` derive macro (from the
+//!     `doublecount_derive` package), which generates the boilerplate
+//!     required to store custom actions in a [Program](Program)
 //!
 //! # Usage
 //!
@@ -116,6 +121,10 @@
 //! program_state.execute_program(&program).unwrap();
 //! ```
 
+// `Option::is_none_or()` and `usize::is_multiple_of()` are avoided so
+// that the crate builds with older versions of the compiler.
+#![allow(clippy::unnecessary_map_or, clippy::manual_is_multiple_of)]
+
 extern crate arrayvec;
 extern crate chrono;
 extern crate commodity;
@@ -132,13 +141,48 @@ extern crate serde_json;
 
 mod account;
 mod actions;
+mod capital_gains;
+mod constraint;
+mod conversion;
+mod deferral;
+mod depreciation;
 mod error;
+mod forecast;
+mod integrity;
+mod interest;
+mod loan;
+mod lot;
+mod ordering;
+mod price;
 mod program;
+mod query;
+mod rounding;
+mod schedule;
+mod valuation;
 
 pub use account::*;
 pub use actions::*;
+pub use capital_gains::*;
+pub use constraint::*;
+pub use conversion::*;
+pub use deferral::*;
+pub use depreciation::*;
 pub use error::AccountingError;
+pub use forecast::*;
+pub use integrity::*;
+pub use interest::*;
+pub use loan::*;
+pub use lot::*;
+pub use ordering::*;
+pub use price::*;
 pub use program::*;
+pub use query::*;
+pub use rounding::*;
+pub use schedule::*;
+pub use valuation::*;
+
+#[cfg(feature = "derive")]
+pub use doublecount_derive::ActionTypeValueEnum;
 
 #[cfg(doctest)]
 #[macro_use]
@@ -0,0 +1,467 @@
+use super::{
+    AccountID, AccountingError, Recurrence, RoundingPolicy, Transaction, TransactionElement,
+    TransactionGenerator,
+};
+use chrono::NaiveDate;
+use commodity::Commodity;
+use rust_decimal::{prelude::ToPrimitive, Decimal, MathematicalOps};
+
+#[cfg(feature = "serde-support")]
+use serde::{Deserialize, Serialize};
+
+/// How often the interest on a [Loan](Loan) is compounded.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Compounding {
+    /// Interest is compounded once per payment period.
+    PerPayment,
+    /// Interest is compounded every day (365 times a year).
+    Daily,
+    /// Interest is compounded every month.
+    Monthly,
+    /// Interest is compounded every quarter.
+    Quarterly,
+    /// Interest is compounded every half year.
+    SemiAnnually,
+    /// Interest is compounded every year.
+    Annually,
+}
+
+impl Compounding {
+    /// The number of times interest is compounded in a year, or
+    /// `None` for [PerPayment](Compounding::PerPayment).
+    pub fn periods_per_year(&self) -> Option<Decimal> {
+        match self {
+            Compounding::PerPayment => None,
+            Compounding::Daily => Some(Decimal::new(365, 0)),
+            Compounding::Monthly => Some(Decimal::new(12, 0)),
+            Compounding::Quarterly => Some(Decimal::new(4, 0)),
+            Compounding::SemiAnnually => Some(Decimal::new(2, 0)),
+            Compounding::Annually => Some(Decimal::new(1, 0)),
+        }
+    }
+}
+
+/// The accounts involved in the repayment of a [Loan](Loan).
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoanAccounts {
+    /// The liability account holding the outstanding principal,
+    /// which receives the principal portion of each payment.
+    pub loan_account_id: AccountID,
+    /// The expense account which receives the interest portion of
+    /// each payment.
+    pub interest_account_id: AccountID,
+    /// The account that each payment is made from.
+    pub payment_account_id: AccountID,
+}
+
+/// A row in the amortization table of a [Loan](Loan), describing a
+/// single payment.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmortizationRow {
+    /// The number of the payment, starting at `1`.
+    pub period: usize,
+    /// The date of the payment.
+    pub date: NaiveDate,
+    /// The total amount paid.
+    pub payment: Commodity,
+    /// The portion of the payment which is interest.
+    pub interest: Commodity,
+    /// The portion of the payment which repays the principal.
+    pub principal: Commodity,
+    /// The principal outstanding after the payment.
+    pub balance: Commodity,
+}
+
+/// An amortizing loan, repaid with equal payments (apart from the
+/// final payment) on the dates of a [Recurrence](Recurrence).
+///
+/// Each payment is split between the interest accrued on the
+/// outstanding principal during the period, and repayment of the
+/// principal. Amounts are rounded using the `rounding` policy, and the
+/// final payment is adjusted to repay exactly the remaining principal.
+///
+/// As a [TransactionGenerator](TransactionGenerator), a transaction is
+/// generated for each payment, from the `payment_account_id` to the
+/// `interest_account_id` and the `loan_account_id`. The initial
+/// disbursement of the loan is not included.
+///
+/// # Example
+/// ```
+/// use doublecount::{AccountID, Loan, LoanAccounts, Recurrence};
+/// use commodity::Commodity;
+/// use chrono::NaiveDate;
+/// use rust_decimal::Decimal;
+/// use std::str::FromStr;
+///
+/// let loan = Loan::new(
+///     Commodity::from_str("10000 USD").unwrap(),
+///     Decimal::from_str("0.06").unwrap(),
+///     12,
+///     NaiveDate::from_ymd_opt(2020, 2, 1).unwrap(),
+///     Recurrence::Monthly,
+///     LoanAccounts {
+///         loan_account_id: AccountID::from("Loan").unwrap(),
+///         interest_account_id: AccountID::from("Interest").unwrap(),
+///         payment_account_id: AccountID::from("Bank").unwrap(),
+///     },
+/// );
+///
+/// let table = loan.amortization_table().unwrap();
+/// assert_eq!(12, table.len());
+/// assert_eq!(Commodity::from_str("860.66 USD").unwrap(), table[0].payment);
+/// assert_eq!(Commodity::from_str("50.00 USD").unwrap(), table[0].interest);
+/// assert_eq!(Commodity::from_str("0 USD").unwrap(), table[11].balance);
+/// ```
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loan {
+    /// The amount borrowed.
+    pub principal: Commodity,
+    /// The nominal annual interest rate (e.g. `0.05` for 5%).
+    pub annual_rate: Decimal,
+    /// How often the interest is compounded.
+    pub compounding: Compounding,
+    /// The number of payments.
+    pub term: usize,
+    /// The date of the first payment.
+    pub first_payment_date: NaiveDate,
+    /// The rule used to determine the dates of the payments.
+    pub recurrence: Recurrence,
+    /// The accounts involved in the repayment.
+    pub accounts: LoanAccounts,
+    /// The amount of each payment. If `None`, the payment required to
+    /// repay the loan within the `term` is calculated.
+    pub payment: Option<Commodity>,
+    /// The policy used to round the payment and the interest.
+    pub rounding: RoundingPolicy,
+}
+
+impl Loan {
+    /// Create a new [Loan](Loan), with interest compounded once per
+    /// payment period and the default [RoundingPolicy](RoundingPolicy).
+    pub fn new(
+        principal: Commodity,
+        annual_rate: Decimal,
+        term: usize,
+        first_payment_date: NaiveDate,
+        recurrence: Recurrence,
+        accounts: LoanAccounts,
+    ) -> Loan {
+        Loan {
+            principal,
+            annual_rate,
+            compounding: Compounding::PerPayment,
+            term,
+            first_payment_date,
+            recurrence,
+            accounts,
+            payment: None,
+            rounding: RoundingPolicy::default(),
+        }
+    }
+
+    /// Set how often the interest is compounded.
+    pub fn with_compounding(mut self, compounding: Compounding) -> Loan {
+        self.compounding = compounding;
+        self
+    }
+
+    /// Set the amount of each payment, instead of calculating it.
+    pub fn with_payment(mut self, payment: Commodity) -> Loan {
+        self.payment = Some(payment);
+        self
+    }
+
+    /// Set the policy used to round the payment and the interest.
+    pub fn with_rounding(mut self, rounding: RoundingPolicy) -> Loan {
+        self.rounding = rounding;
+        self
+    }
+
+    /// The effective interest rate for each payment period.
+    pub fn periodic_rate(&self) -> Result<Decimal, AccountingError> {
+        let payments_per_year = self.recurrence.periods_per_year();
+        if payments_per_year <= Decimal::ZERO {
+            return Err(AccountingError::InvalidLoan(format!(
+                "invalid payment recurrence {:?}",
+                self.recurrence
+            )));
+        }
+
+        match self.compounding.periods_per_year() {
+            None => Ok(self.annual_rate / payments_per_year),
+            Some(compounds_per_year) => {
+                let base = Decimal::new(1, 0) + self.annual_rate / compounds_per_year;
+                let exponent = compounds_per_year / payments_per_year;
+                let growth = match exponent.to_u64().filter(|_| exponent.fract().is_zero()) {
+                    Some(exponent) => base.checked_powu(exponent),
+                    None => base.checked_powd(exponent),
+                };
+                growth
+                    .map(|growth| growth - Decimal::new(1, 0))
+                    .ok_or_else(|| {
+                        AccountingError::InvalidLoan(String::from(
+                            "overflow while calculating the periodic interest rate",
+                        ))
+                    })
+            }
+        }
+    }
+
+    /// The amount of each payment (apart from the final payment),
+    /// either as specified, or calculated to repay the loan within
+    /// the `term`.
+    pub fn periodic_payment(&self) -> Result<Commodity, AccountingError> {
+        if let Some(payment) = self.payment {
+            return Ok(payment);
+        }
+
+        if self.term == 0 {
+            return Err(AccountingError::InvalidLoan(String::from(
+                "the term must be at least one payment",
+            )));
+        }
+
+        let rate = self.periodic_rate()?;
+        let principal = self.principal.value;
+        let term = Decimal::from(self.term);
+
+        let payment = if rate.is_zero() {
+            principal / term
+        } else {
+            let growth = (Decimal::new(1, 0) + rate)
+                .checked_powu(self.term as u64)
+                .ok_or_else(|| {
+                    AccountingError::InvalidLoan(String::from(
+                        "overflow while calculating the payment",
+                    ))
+                })?;
+            principal * rate * growth / (growth - Decimal::new(1, 0))
+        };
+
+        Ok(Commodity::new(
+            self.rounding.round(payment),
+            self.principal.type_id,
+        ))
+    }
+
+    /// Calculate the amortization table for this loan, with a row
+    /// for each payment. The table ends early if the principal is
+    /// repaid before the end of the `term`. Returns an
+    /// [InvalidLoan](AccountingError::InvalidLoan) error if the
+    /// payment does not cover the interest for the first period, in
+    /// which case the loan would never be repaid.
+    pub fn amortization_table(&self) -> Result<Vec<AmortizationRow>, AccountingError> {
+        let rate = self.periodic_rate()?;
+        let payment = self.periodic_payment()?;
+        let type_id = self.principal.type_id;
+
+        if payment.type_id != type_id {
+            return Err(AccountingError::InvalidLoan(format!(
+                "the payment {} is not in the same commodity as the principal {}",
+                payment, self.principal
+            )));
+        }
+
+        let first_interest = self.rounding.round(self.principal.value * rate);
+        if payment.value < first_interest {
+            return Err(AccountingError::InvalidLoan(format!(
+                "the payment {} does not cover the interest {} for the first period",
+                payment,
+                Commodity::new(first_interest, type_id)
+            )));
+        }
+
+        let mut balance = self.principal.value;
+        let mut table = Vec::with_capacity(self.term);
+
+        for (i, date) in self
+            .recurrence
+            .dates(self.first_payment_date)
+            .take(self.term)
+            .enumerate()
+        {
+            let period = i + 1;
+            let interest = self.rounding.round(balance * rate);
+
+            let principal = if period == self.term || payment.value - interest >= balance {
+                balance
+            } else {
+                payment.value - interest
+            };
+
+            balance -= principal;
+
+            table.push(AmortizationRow {
+                period,
+                date,
+                payment: Commodity::new(principal + interest, type_id),
+                interest: Commodity::new(interest, type_id),
+                principal: Commodity::new(principal, type_id),
+                balance: Commodity::new(balance, type_id),
+            });
+
+            if balance.is_zero() {
+                break;
+            }
+        }
+
+        Ok(table)
+    }
+}
+
+impl TransactionGenerator for Loan {
+    fn generate(&self) -> Result<Vec<Transaction>, AccountingError> {
+        let table = self.amortization_table()?;
+        let term = table.len();
+
+        Ok(table
+            .into_iter()
+            .map(|row| {
+                Transaction::new(
+                    Some(format!("Loan payment {} of {}", row.period, term)),
+                    row.date,
+                    vec![
+                        TransactionElement::new(
+                            self.accounts.payment_account_id,
+                            Some(row.payment.neg()),
+                            None,
+                        ),
+                        TransactionElement::new(
+                            self.accounts.interest_account_id,
+                            Some(row.interest),
+                            None,
+                        ),
+                        TransactionElement::new(
+                            self.accounts.loan_account_id,
+                            Some(row.principal),
+                            None,
+                        ),
+                    ],
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compounding, Loan, LoanAccounts};
+    use crate::{
+        Account, AccountStatus, AccountingError, ActionTypeValue, Program, ProgramState,
+        Recurrence, TransactionGenerator,
+    };
+    use chrono::NaiveDate;
+    use commodity::{Commodity, CommodityTypeID};
+    use rust_decimal::Decimal;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    #[test]
+    fn loan() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let loan_account = Rc::from(Account::new_with_id(Some("Loan"), usd, None));
+        let interest_account = Rc::from(Account::new_with_id(Some("Interest"), usd, None));
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), usd, None));
+
+        let loan = Loan::new(
+            Commodity::from_str("1000 USD").unwrap(),
+            Decimal::from_str("0.12").unwrap(),
+            3,
+            NaiveDate::from_ymd_opt(2020, 1, 31).unwrap(),
+            Recurrence::Monthly,
+            LoanAccounts {
+                loan_account_id: loan_account.id,
+                interest_account_id: interest_account.id,
+                payment_account_id: bank.id,
+            },
+        );
+
+        let table = loan.amortization_table().unwrap();
+        assert_eq!(3, table.len());
+        assert_eq!(Commodity::from_str("340.02 USD").unwrap(), table[0].payment);
+        assert_eq!(Commodity::from_str("10.00 USD").unwrap(), table[0].interest);
+        assert_eq!(Commodity::from_str("6.70 USD").unwrap(), table[1].interest);
+        assert_eq!(NaiveDate::from_ymd_opt(2020, 2, 29).unwrap(), table[1].date);
+        // the final payment is adjusted for rounding
+        assert_eq!(Commodity::from_str("340.03 USD").unwrap(), table[2].payment);
+        assert_eq!(Commodity::from_str("0 USD").unwrap(), table[2].balance);
+
+        let actions: Vec<Rc<ActionTypeValue>> = loan.actions().unwrap();
+        let program = Program::new(actions);
+        let mut program_state = ProgramState::new(
+            &[loan_account.clone(), interest_account.clone(), bank.clone()],
+            AccountStatus::Open,
+        );
+        program_state.execute_program(&program).unwrap();
+
+        assert_eq!(
+            Commodity::from_str("1000 USD").unwrap(),
+            program_state
+                .get_account_state(&loan_account.id)
+                .unwrap()
+                .amount
+        );
+        assert_eq!(
+            Commodity::from_str("20.07 USD").unwrap(),
+            program_state
+                .get_account_state(&interest_account.id)
+                .unwrap()
+                .amount
+        );
+
+        // 12% compounded annually is an effective rate of
+        // approximately 0.9489% per month.
+        let rate = loan
+            .with_compounding(Compounding::Annually)
+            .periodic_rate()
+            .unwrap();
+        assert_eq!(Decimal::from_str("0.009489").unwrap(), rate.round_dp(6));
+    }
+
+    #[test]
+    fn loan_payment_less_than_interest() {
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let loan = Loan::new(
+            Commodity::from_str("1000 USD").unwrap(),
+            Decimal::from_str("0.12").unwrap(),
+            3,
+            NaiveDate::from_ymd_opt(2020, 1, 31).unwrap(),
+            Recurrence::Monthly,
+            LoanAccounts {
+                loan_account_id: Account::new_with_id(Some("Loan"), usd, None).id,
+                interest_account_id: Account::new_with_id(Some("Interest"), usd, None).id,
+                payment_account_id: Account::new_with_id(Some("Bank"), usd, None).id,
+            },
+        );
+
+        // the first period's interest is 10 USD
+        let error = loan
+            .clone()
+            .with_payment(Commodity::from_str("9.99 USD").unwrap())
+            .amortization_table()
+            .unwrap_err();
+        assert!(matches!(error, AccountingError::InvalidLoan(_)));
+        assert!(loan
+            .clone()
+            .with_payment(Commodity::from_str("9.99 USD").unwrap())
+            .generate()
+            .is_err());
+
+        // an interest only loan repays the principal with the final
+        // payment
+        let table = loan
+            .with_payment(Commodity::from_str("10 USD").unwrap())
+            .amortization_table()
+            .unwrap();
+        assert_eq!(3, table.len());
+        assert_eq!(Commodity::from_str("1000 USD").unwrap(), table[1].balance);
+        assert_eq!(
+            Commodity::from_str("1010.00 USD").unwrap(),
+            table[2].payment
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use super::AccountID;
+use chrono::NaiveDate;
+use commodity::{Commodity, CommodityError};
+use rust_decimal::Decimal;
+use std::fmt;
+
+#[cfg(feature = "serde-support")]
+use serde::{Deserialize, Serialize};
+
+/// The method used to select which [Lot](Lot)s are reduced when
+/// units of a commodity leave an [Account](crate::Account) which
+/// tracks lots.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BookingMethod {
+    /// First in, first out. The lots acquired earliest are reduced first.
+    Fifo,
+    /// Last in, first out. The lots acquired most recently are reduced first.
+    Lifo,
+    /// Highest in, first out. The lots with the highest cost per unit
+    /// are reduced first.
+    Hifo,
+    /// The lots are reduced in the order they were acquired, at the
+    /// average cost per unit of all the lots held. The remaining lots
+    /// are then valued at this average cost.
+    AverageCost,
+    /// The lot to reduce must be specified using the `lot_label` of
+    /// the [TransactionElement](crate::TransactionElement).
+    SpecificLot,
+}
+
+/// A quantity of a commodity held in an [Account](crate::Account),
+/// which was acquired on a given date at a given cost per unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lot {
+    /// The quantity of the commodity remaining in this lot.
+    pub quantity: Commodity,
+    /// The cost of each unit in this lot.
+    pub cost: Commodity,
+    /// The date that this lot was acquired.
+    pub acquisition_date: NaiveDate,
+    /// An optional label used to identify this lot.
+    pub label: Option<String>,
+}
+
+impl Lot {
+    /// Create a new [Lot](Lot).
+    pub fn new(
+        quantity: Commodity,
+        cost: Commodity,
+        acquisition_date: NaiveDate,
+        label: Option<String>,
+    ) -> Lot {
+        Lot {
+            quantity,
+            cost,
+            acquisition_date,
+            label,
+        }
+    }
+
+    /// The total cost of the quantity remaining in this lot.
+    pub fn cost_basis(&self) -> Commodity {
+        Commodity::new(self.quantity.value * self.cost.value, self.cost.type_id)
+    }
+}
+
+/// A reduction of part or all of a [Lot](Lot), selected using a
+/// [BookingMethod](BookingMethod).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LotReduction {
+    /// The index of the lot being reduced in the account's lots.
+    pub index: usize,
+    /// The quantity to remove from the lot.
+    pub quantity: Commodity,
+    /// The cost of each unit being removed.
+    pub cost: Commodity,
+}
+
+impl LotReduction {
+    /// The total cost of the quantity being removed.
+    pub fn cost_basis(&self) -> Commodity {
+        Commodity::new(self.quantity.value * self.cost.value, self.cost.type_id)
+    }
+}
+
+/// The reason why a reduction of lots could not be booked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookingError {
+    /// There are not enough units held in the (matching) lots.
+    InsufficientLots(Commodity),
+    /// The [SpecificLot](BookingMethod::SpecificLot) method was used
+    /// without specifying a lot label.
+    NoLotSpecified,
+    /// A lot label was specified with the
+    /// [AverageCost](BookingMethod::AverageCost) method, which
+    /// reduces lots at the average cost of all the lots held.
+    LotSpecifiedWithAverageCost,
+}
+
+impl fmt::Display for BookingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookingError::InsufficientLots(quantity) => {
+                write!(f, "insufficient lots held to reduce {}", quantity)
+            }
+            BookingError::NoLotSpecified => {
+                write!(f, "a lot label is required to reduce a specific lot")
+            }
+            BookingError::LotSpecifiedWithAverageCost => write!(
+                f,
+                "a lot label cannot be used to reduce lots at the average cost"
+            ),
+        }
+    }
+}
+
+/// Select the reductions of `lots` to remove the (positive) `quantity`
+/// of a commodity, using the given booking `method`. If a `label` is
+/// supplied, only lots with that label will be reduced (a label cannot
+/// be used with the [AverageCost](BookingMethod::AverageCost) method,
+/// because the remaining lots are all valued at the average cost).
+pub fn book_lots(
+    lots: &[Lot],
+    quantity: Commodity,
+    method: BookingMethod,
+    label: Option<&str>,
+) -> Result<Vec<LotReduction>, BookingError> {
+    if method == BookingMethod::SpecificLot && label.is_none() {
+        return Err(BookingError::NoLotSpecified);
+    }
+
+    if method == BookingMethod::AverageCost && label.is_some() {
+        return Err(BookingError::LotSpecifiedWithAverageCost);
+    }
+
+    let mut candidates: Vec<usize> = lots
+        .iter()
+        .enumerate()
+        .filter(|(_, lot)| lot.quantity.type_id == quantity.type_id)
+        .filter(|(_, lot)| label.is_none() || lot.label.as_deref() == label)
+        .map(|(i, _)| i)
+        .collect();
+
+    match method {
+        BookingMethod::Fifo | BookingMethod::AverageCost | BookingMethod::SpecificLot => {
+            candidates.sort_by_key(|i| lots[*i].acquisition_date)
+        }
+        BookingMethod::Lifo => {
+            candidates.sort_by_key(|i| std::cmp::Reverse(lots[*i].acquisition_date))
+        }
+        BookingMethod::Hifo => {
+            candidates.sort_by(|a, b| lots[*b].cost.value.cmp(&lots[*a].cost.value))
+        }
+    }
+
+    let average_cost = if method == BookingMethod::AverageCost {
+        let total_quantity: Decimal = candidates.iter().map(|i| lots[*i].quantity.value).sum();
+        let total_cost: Decimal = candidates.iter().map(|i| lots[*i].cost_basis().value).sum();
+        candidates
+            .first()
+            .filter(|_| !total_quantity.is_zero())
+            .map(|i| Commodity::new(total_cost / total_quantity, lots[*i].cost.type_id))
+    } else {
+        None
+    };
+
+    let mut remaining = quantity.value;
+    let mut reductions = Vec::new();
+
+    for index in candidates {
+        if remaining.is_zero() {
+            break;
+        }
+
+        let lot = &lots[index];
+        let reduce = remaining.min(lot.quantity.value);
+        if reduce <= Decimal::ZERO {
+            continue;
+        }
+
+        reductions.push(LotReduction {
+            index,
+            quantity: Commodity::new(reduce, quantity.type_id),
+            cost: average_cost.unwrap_or(lot.cost),
+        });
+        remaining -= reduce;
+    }
+
+    if !remaining.is_zero() {
+        return Err(BookingError::InsufficientLots(quantity));
+    }
+
+    Ok(reductions)
+}
+
+/// Apply `reductions` (created using [book_lots()](book_lots())) to
+/// `lots`, removing any lots which are left empty. If the
+/// `method` is [AverageCost](BookingMethod::AverageCost), the remaining
+/// lots of the reduced commodity type are valued at the average cost.
+pub fn apply_lot_reductions(
+    lots: &mut Vec<Lot>,
+    reductions: &[LotReduction],
+    method: BookingMethod,
+) -> Result<(), CommodityError> {
+    for reduction in reductions {
+        let lot = &mut lots[reduction.index];
+        lot.quantity = lot.quantity.sub(&reduction.quantity)?;
+    }
+
+    if method == BookingMethod::AverageCost {
+        if let Some(reduction) = reductions.first() {
+            for lot in lots.iter_mut() {
+                if lot.quantity.type_id == reduction.quantity.type_id {
+                    lot.cost = reduction.cost;
+                }
+            }
+        }
+    }
+
+    lots.retain(|lot| !lot.quantity.value.is_zero());
+    Ok(())
+}
+
+/// A record of the disposal of (part of) a [Lot](Lot), with the
+/// resulting realized gain or loss.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disposal {
+    /// The id of the account that the lot was held in.
+    pub account_id: AccountID,
+    /// The quantity disposed of.
+    pub quantity: Commodity,
+    /// The date that the lot was acquired.
+    pub acquisition_date: NaiveDate,
+    /// The date of the disposal.
+    pub disposal_date: NaiveDate,
+    /// The label of the lot.
+    pub lot_label: Option<String>,
+    /// The cost of the quantity disposed of.
+    pub cost_basis: Commodity,
+    /// The amount received for the quantity disposed of.
+    pub proceeds: Commodity,
+    /// The gain realized by the disposal (`proceeds - cost_basis`).
+    /// A negative value represents a loss.
+    pub gain: Commodity,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_lot_reductions, book_lots, BookingError, BookingMethod, Lot};
+    use chrono::NaiveDate;
+    use commodity::Commodity;
+    use std::str::FromStr;
+
+    fn lots() -> Vec<Lot> {
+        vec![
+            Lot::new(
+                Commodity::from_str("10 AAPL").unwrap(),
+                Commodity::from_str("100 USD").unwrap(),
+                NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+                Some("a".to_string()),
+            ),
+            Lot::new(
+                Commodity::from_str("10 AAPL").unwrap(),
+                Commodity::from_str("300 USD").unwrap(),
+                NaiveDate::from_ymd_opt(2020, 2, 1).unwrap(),
+                Some("b".to_string()),
+            ),
+            Lot::new(
+                Commodity::from_str("10 AAPL").unwrap(),
+                Commodity::from_str("200 USD").unwrap(),
+                NaiveDate::from_ymd_opt(2020, 3, 1).unwrap(),
+                Some("c".to_string()),
+            ),
+        ]
+    }
+
+    fn booked_cost(method: BookingMethod, label: Option<&str>) -> Commodity {
+        book_lots(
+            &lots(),
+            Commodity::from_str("15 AAPL").unwrap(),
+            method,
+            label,
+        )
+        .unwrap()
+        .iter()
+        .fold(Commodity::from_str("0 USD").unwrap(), |sum, r| {
+            sum.add(&r.cost_basis()).unwrap()
+        })
+    }
+
+    #[test]
+    fn booking_methods() {
+        assert_eq!(
+            Commodity::from_str("2500 USD").unwrap(),
+            booked_cost(BookingMethod::Fifo, None)
+        );
+        assert_eq!(
+            Commodity::from_str("3500 USD").unwrap(),
+            booked_cost(BookingMethod::Lifo, None)
+        );
+        assert_eq!(
+            Commodity::from_str("4000 USD").unwrap(),
+            booked_cost(BookingMethod::Hifo, None)
+        );
+        assert_eq!(
+            Commodity::from_str("3000 USD").unwrap(),
+            booked_cost(BookingMethod::AverageCost, None)
+        );
+        assert_eq!(
+            Err(BookingError::NoLotSpecified),
+            book_lots(
+                &lots(),
+                Commodity::from_str("5 AAPL").unwrap(),
+                BookingMethod::SpecificLot,
+                None
+            )
+        );
+        assert_eq!(
+            Err(BookingError::LotSpecifiedWithAverageCost),
+            book_lots(
+                &lots(),
+                Commodity::from_str("5 AAPL").unwrap(),
+                BookingMethod::AverageCost,
+                Some("b")
+            )
+        );
+        assert_eq!(
+            Err(BookingError::InsufficientLots(
+                Commodity::from_str("15 AAPL").unwrap()
+            )),
+            book_lots(
+                &lots(),
+                Commodity::from_str("15 AAPL").unwrap(),
+                BookingMethod::SpecificLot,
+                Some("b")
+            )
+        );
+    }
+
+    #[test]
+    fn apply_reductions() {
+        let mut lots = lots();
+        let reductions = book_lots(
+            &lots,
+            Commodity::from_str("15 AAPL").unwrap(),
+            BookingMethod::AverageCost,
+            None,
+        )
+        .unwrap();
+        apply_lot_reductions(&mut lots, &reductions, BookingMethod::AverageCost).unwrap();
+
+        assert_eq!(2, lots.len());
+        assert_eq!(Commodity::from_str("5 AAPL").unwrap(), lots[0].quantity);
+        assert_eq!(Commodity::from_str("200 USD").unwrap(), lots[0].cost);
+        assert_eq!(Some("b".to_string()), lots[0].label);
+    }
+}
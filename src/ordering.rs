@@ -0,0 +1,282 @@
+use super::{ActionTypeFor, ActionTypeValueEnum};
+use std::cmp::Ordering;
+
+/// A strategy used to order the actions of a
+/// [Program](crate::Program), which can be provided to
+/// [Program::new_with_ordering()](crate::Program::new_with_ordering())
+/// and [Program::from_sorted()](crate::Program::from_sorted()).
+///
+/// Actions are always sorted using a stable sort, so actions which
+/// compare as equal remain in the order that they were provided.
+///
+/// This is implemented for closures, so a custom ordering can be
+/// provided as a function which compares two actions.
+pub trait ActionOrdering<AT, ATV> {
+    /// Compare two actions, the action which is ordered first is
+    /// performed first.
+    fn compare(&self, a: &ATV, b: &ATV) -> Ordering;
+}
+
+impl<AT, ATV, F> ActionOrdering<AT, ATV> for F
+where
+    F: Fn(&ATV, &ATV) -> Ordering,
+{
+    fn compare(&self, a: &ATV, b: &ATV) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// The default [ActionOrdering](ActionOrdering), used by
+/// [Program::new()](crate::Program::new()) and
+/// [ActionOrder](crate::ActionOrder).
+///
+/// Actions are ordered by their date, then by their
+/// [time()](crate::Action::time()), then by their
+/// [sequence()](crate::Action::sequence()), and then by the priority
+/// of their action type. With the default
+/// [ActionType](crate::ActionType) this is similar to the ordering
+/// used by beancount, where balance assertions apply at the start of
+/// the day.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DefaultOrdering;
+
+impl<AT, ATV> ActionOrdering<AT, ATV> for DefaultOrdering
+where
+    AT: Ord,
+    ATV: ActionTypeValueEnum<AT> + ActionTypeFor<AT>,
+{
+    fn compare(&self, a: &ATV, b: &ATV) -> Ordering {
+        let a_action = a.as_action();
+        let b_action = b.as_action();
+        a_action
+            .date()
+            .cmp(&b_action.date())
+            .then(a_action.time().cmp(&b_action.time()))
+            .then(a_action.sequence().cmp(&b_action.sequence()))
+            .then(a.action_type().cmp(&b.action_type()))
+    }
+}
+
+/// An [ActionOrdering](ActionOrdering) which ignores the priority of
+/// action types, so that actions at the same date, time and sequence
+/// remain in the order that they were written.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AsWrittenOrdering;
+
+impl<AT, ATV> ActionOrdering<AT, ATV> for AsWrittenOrdering
+where
+    ATV: ActionTypeValueEnum<AT>,
+{
+    fn compare(&self, a: &ATV, b: &ATV) -> Ordering {
+        let a_action = a.as_action();
+        let b_action = b.as_action();
+        a_action
+            .date()
+            .cmp(&b_action.date())
+            .then(a_action.time().cmp(&b_action.time()))
+            .then(a_action.sequence().cmp(&b_action.sequence()))
+    }
+}
+
+/// An [ActionOrdering](ActionOrdering) where
+/// [BalanceAssertion](crate::BalanceAssertion)s without a time of day
+/// apply at the end of their date (after every other action on that
+/// date), instead of at the start. Otherwise this is the same as the
+/// [DefaultOrdering](DefaultOrdering).
+///
+/// This is useful for imported formats which define balance
+/// assertions as end of day balances.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EndOfDayAssertionOrdering;
+
+impl<AT, ATV> ActionOrdering<AT, ATV> for EndOfDayAssertionOrdering
+where
+    AT: Ord,
+    ATV: ActionTypeValueEnum<AT> + ActionTypeFor<AT>,
+{
+    fn compare(&self, a: &ATV, b: &ATV) -> Ordering {
+        let end_of_day = |action: &ATV| {
+            let action = action.as_action();
+            action.as_balance_assertion().is_some() && action.time().is_none()
+        };
+
+        a.as_action()
+            .date()
+            .cmp(&b.as_action().date())
+            .then(end_of_day(a).cmp(&end_of_day(b)))
+            .then_with(|| DefaultOrdering.compare(a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActionOrdering, AsWrittenOrdering, EndOfDayAssertionOrdering};
+    use crate::{
+        Account, AccountID, AccountStatus, AccountingError, ActionType, ActionTypeFor,
+        ActionTypeValue, BalanceAssertion, Program, ProgramState, Transaction,
+    };
+    use chrono::{NaiveDate, NaiveTime};
+    use commodity::{Commodity, CommodityTypeID};
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    fn action_types(actions: &[Rc<ActionTypeValue>]) -> Vec<ActionType> {
+        actions.iter().map(|action| action.action_type()).collect()
+    }
+
+    /// The actual balance of the first assertion which fails when
+    /// the `program` is executed, if any.
+    fn failed_balance<O>(
+        accounts: &[Rc<Account>],
+        program: &Program<ActionType, ActionTypeValue, O>,
+    ) -> Option<Commodity> {
+        let mut program_state = ProgramState::new(accounts, AccountStatus::Open);
+        match program_state.execute_program(program) {
+            Ok(()) => None,
+            Err(AccountingError::BalanceAssertionFailed(failed)) => Some(failed.actual_balance),
+            Err(error) => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn orderings() {
+        let date = |d| NaiveDate::from_ymd_opt(2020, 1, d).unwrap();
+        let account1 = AccountID::from("Account1").unwrap();
+        let account2 = AccountID::from("Account2").unwrap();
+        let amount = Commodity::from_str("1 AUD").unwrap();
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(
+                Transaction::new_simple::<String>(None, date(1), account1, account2, amount, None)
+                    .into(),
+            ),
+            Rc::new(BalanceAssertion::new(account2, date(1), amount).into()),
+            Rc::new(BalanceAssertion::new(account2, date(2), amount).into()),
+            Rc::new(
+                Transaction::new_simple::<String>(None, date(2), account1, account2, amount, None)
+                    .into(),
+            ),
+        ];
+
+        assert_eq!(
+            vec![
+                ActionType::BalanceAssertion,
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+                ActionType::Transaction,
+            ],
+            action_types(&Program::new(actions.clone()).actions)
+        );
+        assert_eq!(
+            vec![
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+                ActionType::BalanceAssertion,
+                ActionType::Transaction,
+            ],
+            action_types(&Program::new_with_ordering(actions.clone(), &AsWrittenOrdering).actions)
+        );
+
+        let end_of_day = Program::new_with_ordering(actions.clone(), &EndOfDayAssertionOrdering);
+        assert_eq!(
+            vec![
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+            ],
+            action_types(&end_of_day.actions)
+        );
+
+        // reverse the order of actions within each day
+        let reversed = |a: &ActionTypeValue, b: &ActionTypeValue| {
+            ActionOrdering::<ActionType, ActionTypeValue>::compare(&AsWrittenOrdering, a, b).then(
+                ActionTypeFor::<ActionType>::action_type(b)
+                    .cmp(&ActionTypeFor::<ActionType>::action_type(a)),
+            )
+        };
+        assert_eq!(
+            vec![
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+            ],
+            action_types(&Program::new_with_ordering(actions.clone(), &reversed).actions)
+        );
+
+        assert!(Program::from_sorted(end_of_day.actions, &EndOfDayAssertionOrdering).is_ok());
+        match Program::from_sorted(actions, &EndOfDayAssertionOrdering) {
+            Err(AccountingError::UnsortedActions(index)) => assert_eq!(3, index),
+            _ => panic!("Expected an AccountingError::UnsortedActions"),
+        }
+    }
+
+    #[test]
+    fn orderings_same_date() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let time = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let aud = CommodityTypeID::from_str("AUD").unwrap();
+        let account1 = Rc::from(Account::new_with_id(Some("Account1"), aud, None));
+        let account2 = Rc::from(Account::new_with_id(Some("Account2"), aud, None));
+        let amount = |s| Commodity::from_str(s).unwrap();
+        let transfer = || {
+            Transaction::new_simple::<String>(
+                None,
+                date,
+                account1.id,
+                account2.id,
+                amount("1 AUD"),
+                None,
+            )
+        };
+
+        // an assertion without a time is written before the
+        // transactions on the same date, and an assertion with a time
+        // is written between them.
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(BalanceAssertion::new(account2.id, date, amount("2 AUD")).into()),
+            Rc::new(transfer().into()),
+            Rc::new(
+                BalanceAssertion::new(account2.id, date, amount("1 AUD"))
+                    .with_time(time(12))
+                    .into(),
+            ),
+            Rc::new(transfer().with_time(time(13)).into()),
+        ];
+
+        let accounts = vec![account1, account2];
+
+        let as_written = Program::new_with_ordering(actions.clone(), &AsWrittenOrdering);
+        assert_eq!(
+            vec![
+                ActionType::BalanceAssertion,
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+                ActionType::Transaction,
+            ],
+            action_types(&as_written.actions)
+        );
+        // the assertion without a time is performed before the
+        // transactions
+        assert_eq!(
+            Some(amount("0 AUD")),
+            failed_balance(&accounts, &as_written)
+        );
+
+        // only the assertion without a time is moved to the end of
+        // the day, the assertion with a time stays between the
+        // transactions
+        let end_of_day = Program::new_with_ordering(actions, &EndOfDayAssertionOrdering);
+        assert_eq!(
+            vec![
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+                ActionType::Transaction,
+                ActionType::BalanceAssertion,
+            ],
+            action_types(&end_of_day.actions)
+        );
+        assert_eq!(None, failed_balance(&accounts, &end_of_day));
+    }
+}
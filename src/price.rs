@@ -0,0 +1,322 @@
+use super::AccountingError;
+use chrono::NaiveDate;
+use commodity::exchange_rate::ExchangeRate;
+use commodity::{Commodity, CommodityTypeID};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+
+/// The method to use when looking up the price of a
+/// [CommodityType](commodity::CommodityType) on a given date in a
+/// [PriceDatabase](PriceDatabase).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PriceLookup {
+    /// Use the latest price recorded on or before the date.
+    LatestBefore,
+    /// Linearly interpolate between the latest price recorded on or
+    /// before the date, and the earliest price recorded after the
+    /// date. If there is no price recorded after the date, this
+    /// behaves the same as [LatestBefore](PriceLookup::LatestBefore).
+    Interpolate,
+}
+
+/// A database of the prices of
+/// [CommodityType](commodity::CommodityType)s over time, populated
+/// by [Price](crate::Price) actions while a
+/// [Program](crate::Program) is being executed.
+///
+/// Prices are stored for pairs of commodity types, and can be
+/// queried in either direction.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceDatabase {
+    /// Maps a `(base, quote)` pair of commodity type ids to the
+    /// price of one unit of `base` in `quote`, on each date it was
+    /// recorded.
+    prices: HashMap<(CommodityTypeID, CommodityTypeID), BTreeMap<NaiveDate, Decimal>>,
+}
+
+impl PriceDatabase {
+    /// Create a new empty [PriceDatabase](PriceDatabase).
+    pub fn new() -> PriceDatabase {
+        PriceDatabase::default()
+    }
+
+    /// Record the `price` of one unit of the commodity type `base`
+    /// on the given `date`. This replaces any price previously
+    /// recorded for the same pair on the same date.
+    pub fn insert(&mut self, date: NaiveDate, base: CommodityTypeID, price: Commodity) {
+        self.prices
+            .entry((base, price.type_id))
+            .or_default()
+            .insert(date, price.value);
+    }
+
+    /// Returns true if there are no prices recorded in this database.
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+
+    /// The commodity type id pairs `(base, quote)` which have prices
+    /// recorded in this database.
+    pub fn pairs(&self) -> impl Iterator<Item = &(CommodityTypeID, CommodityTypeID)> {
+        self.prices.keys()
+    }
+
+    /// Get the rate to convert one unit of the commodity type `from`
+    /// into the commodity type `to`, effective on the given `date`.
+    /// Returns `None` if no price is available.
+    ///
+    /// # Example
+    /// ```
+    /// use doublecount::{PriceDatabase, PriceLookup};
+    /// use commodity::{Commodity, CommodityTypeID};
+    /// use chrono::NaiveDate;
+    /// use rust_decimal::Decimal;
+    /// use std::str::FromStr;
+    ///
+    /// let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+    /// let usd = CommodityTypeID::from_str("USD").unwrap();
+    ///
+    /// let mut database = PriceDatabase::new();
+    /// database.insert(
+    ///     NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+    ///     aapl,
+    ///     Commodity::from_str("300.0 USD").unwrap(),
+    /// );
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
+    /// assert_eq!(
+    ///     Some(Decimal::from_str("300.0").unwrap()),
+    ///     database.get_rate(&aapl, &usd, date, PriceLookup::LatestBefore)
+    /// );
+    /// ```
+    pub fn get_rate(
+        &self,
+        from: &CommodityTypeID,
+        to: &CommodityTypeID,
+        date: NaiveDate,
+        lookup: PriceLookup,
+    ) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::new(1, 0));
+        }
+
+        if let Some(rate) = self
+            .prices
+            .get(&(*from, *to))
+            .and_then(|prices| Self::lookup_rate(prices, date, lookup))
+        {
+            return Some(rate);
+        }
+
+        self.prices
+            .get(&(*to, *from))
+            .and_then(|prices| Self::lookup_rate(prices, date, lookup))
+            .filter(|rate| !rate.is_zero())
+            .and_then(|rate| Decimal::new(1, 0).checked_div(rate))
+    }
+
+    /// Get the price of one unit of the commodity type `base` in the
+    /// commodity type `quote`, effective on the given `date`.
+    pub fn get_price(
+        &self,
+        base: &CommodityTypeID,
+        quote: &CommodityTypeID,
+        date: NaiveDate,
+        lookup: PriceLookup,
+    ) -> Option<Commodity> {
+        self.get_rate(base, quote, date, lookup)
+            .map(|rate| Commodity::new(rate, *quote))
+    }
+
+    /// Convert a `commodity` into the commodity type `to`, using the
+    /// price effective on the given `date`.
+    pub fn convert(
+        &self,
+        commodity: Commodity,
+        to: CommodityTypeID,
+        date: NaiveDate,
+        lookup: PriceLookup,
+    ) -> Result<Commodity, AccountingError> {
+        match self.get_rate(&commodity.type_id, &to, date, lookup) {
+            Some(rate) => Ok(commodity.convert(to, rate)),
+            None => Err(AccountingError::NoPriceAvailable(commodity, to, date)),
+        }
+    }
+
+    /// Create an [ExchangeRate](ExchangeRate) with the given `base`,
+    /// containing the rate for every commodity type which has a price
+    /// against `base` effective on the given `date`. This can be used
+    /// with [sum_account_states()](crate::sum_account_states()).
+    pub fn exchange_rate(
+        &self,
+        base: CommodityTypeID,
+        date: NaiveDate,
+        lookup: PriceLookup,
+    ) -> ExchangeRate {
+        let mut rates = BTreeMap::new();
+
+        for (pair_base, pair_quote) in self.prices.keys() {
+            let other = if *pair_base == base {
+                *pair_quote
+            } else if *pair_quote == base {
+                *pair_base
+            } else {
+                continue;
+            };
+
+            // the number of units of `other` for one unit of `base`
+            if let Some(rate) = self.get_rate(&base, &other, date, lookup) {
+                rates.insert(other, rate);
+            }
+        }
+
+        ExchangeRate {
+            date: Some(date),
+            obtained_datetime: None,
+            base: Some(base),
+            rates,
+        }
+    }
+
+    fn lookup_rate(
+        prices: &BTreeMap<NaiveDate, Decimal>,
+        date: NaiveDate,
+        lookup: PriceLookup,
+    ) -> Option<Decimal> {
+        let (before_date, before_rate) = prices.range(..=date).next_back()?;
+
+        if lookup == PriceLookup::LatestBefore || *before_date == date {
+            return Some(*before_rate);
+        }
+
+        match prices.range(date..).next() {
+            Some((after_date, after_rate)) => {
+                let span = Decimal::from((*after_date - *before_date).num_days());
+                let elapsed = Decimal::from((date - *before_date).num_days());
+                Some(*before_rate + (*after_rate - *before_rate) * elapsed / span)
+            }
+            None => Some(*before_rate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PriceDatabase, PriceLookup};
+    use chrono::NaiveDate;
+    use commodity::{Commodity, CommodityTypeID};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    #[test]
+    fn get_rate() {
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+
+        let mut database = PriceDatabase::new();
+        database.insert(
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            aapl,
+            Commodity::from_str("100.0 USD").unwrap(),
+        );
+        database.insert(
+            NaiveDate::from_ymd_opt(2020, 1, 11).unwrap(),
+            aapl,
+            Commodity::from_str("200.0 USD").unwrap(),
+        );
+
+        let before = NaiveDate::from_ymd_opt(2019, 12, 31).unwrap();
+        let between = NaiveDate::from_ymd_opt(2020, 1, 3).unwrap();
+        let after = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
+
+        assert_eq!(
+            None,
+            database.get_rate(&aapl, &usd, before, PriceLookup::LatestBefore)
+        );
+        assert_eq!(
+            Some(Decimal::from_str("100.0").unwrap()),
+            database.get_rate(&aapl, &usd, between, PriceLookup::LatestBefore)
+        );
+        assert_eq!(
+            Some(Decimal::from_str("120.0").unwrap()),
+            database.get_rate(&aapl, &usd, between, PriceLookup::Interpolate)
+        );
+        assert_eq!(
+            Some(Decimal::from_str("200.0").unwrap()),
+            database.get_rate(&aapl, &usd, after, PriceLookup::Interpolate)
+        );
+        assert_eq!(
+            Some(Decimal::from_str("0.01").unwrap()),
+            database.get_rate(&usd, &aapl, between, PriceLookup::LatestBefore)
+        );
+
+        assert_eq!(
+            Commodity::from_str("240.0 USD").unwrap(),
+            database
+                .convert(
+                    Commodity::from_str("2 AAPL").unwrap(),
+                    usd,
+                    between,
+                    PriceLookup::Interpolate
+                )
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn get_rate_inverse_and_interpolate() {
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+        let eur = CommodityTypeID::from_str("EUR").unwrap();
+        let date = |d| NaiveDate::from_ymd_opt(2020, 1, d).unwrap();
+
+        let mut database = PriceDatabase::new();
+        database.insert(date(1), aapl, Commodity::from_str("100.0 USD").unwrap());
+        database.insert(date(11), aapl, Commodity::from_str("300.0 USD").unwrap());
+
+        // halfway between the two prices, and exactly on a recorded
+        // price
+        assert_eq!(
+            Some(Decimal::from_str("200.0").unwrap()),
+            database.get_rate(&aapl, &usd, date(6), PriceLookup::Interpolate)
+        );
+        assert_eq!(
+            Some(Decimal::from_str("300.0").unwrap()),
+            database.get_rate(&aapl, &usd, date(11), PriceLookup::Interpolate)
+        );
+
+        // the inverse rate is the inverse of the interpolated price
+        assert_eq!(
+            Some(Decimal::from_str("0.005").unwrap()),
+            database.get_rate(&usd, &aapl, date(6), PriceLookup::Interpolate)
+        );
+        assert_eq!(
+            Some(Decimal::from_str("0.01").unwrap()),
+            database.get_rate(&usd, &aapl, date(6), PriceLookup::LatestBefore)
+        );
+
+        // a price recorded in the requested direction takes
+        // precedence over the inverse of the other direction
+        database.insert(date(1), usd, Commodity::from_str("0.02 AAPL").unwrap());
+        assert_eq!(
+            Some(Decimal::from_str("0.02").unwrap()),
+            database.get_rate(&usd, &aapl, date(6), PriceLookup::LatestBefore)
+        );
+
+        // a zero price cannot be inverted
+        database.insert(date(1), eur, Commodity::from_str("0 USD").unwrap());
+        assert_eq!(
+            Some(Decimal::ZERO),
+            database.get_rate(&eur, &usd, date(6), PriceLookup::LatestBefore)
+        );
+        assert_eq!(
+            None,
+            database.get_rate(&usd, &eur, date(6), PriceLookup::LatestBefore)
+        );
+
+        assert_eq!(
+            Some(Decimal::new(1, 0)),
+            database.get_rate(&eur, &eur, date(6), PriceLookup::LatestBefore)
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use super::{
-    Account, AccountID, AccountState, AccountStatus, AccountingError, ActionOrder,
-    FailedBalanceAssertion,
+    Account, AccountID, AccountState, AccountStatus, AccountingError, Action, ActionOrder,
+    ActionOrdering, ActionQuery, CheckSumFailure, ConstraintMode, ConstraintViolation,
+    DefaultOrdering, Disposal, FailedBalanceAssertion, Forecast, IntegrityMode, PriceDatabase,
+    Schedule, Transaction,
 };
+use chrono::NaiveDate;
 use commodity::exchange_rate::ExchangeRate;
 use commodity::{Commodity, CommodityTypeID};
-use std::collections::HashMap;
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
 use std::rc::Rc;
 
@@ -14,9 +19,16 @@ use serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Seriali
 
 /// A collection of [Action](Action)s to be executed in order to
 /// mutate some [ProgramState](ProgramState).
+///
+/// The actions are kept in the order given by the program's
+/// [ActionOrdering](ActionOrdering) `O`, which is also used to sort
+/// the actions when the program is deserialized, or combined with
+/// other actions (such as by
+/// [ProgramState::forecast()](ProgramState::forecast())).
 #[derive(Debug, Clone, PartialEq)]
-pub struct Program<AT = ActionType, ATV = ActionTypeValue> {
+pub struct Program<AT = ActionType, ATV = ActionTypeValue, O = DefaultOrdering> {
     pub actions: Vec<Rc<ATV>>,
+    ordering: O,
     action_type: PhantomData<AT>,
 }
 
@@ -33,9 +45,58 @@ where
         sorted_actions.sort_by_key(|a| ActionOrder::new(a.clone()));
         Program {
             actions: sorted_actions,
-            action_type: PhantomData::default(),
+            ordering: DefaultOrdering,
+            action_type: PhantomData,
         }
     }
+}
+
+impl<AT, ATV, O> Program<AT, ATV, O>
+where
+    AT: Ord,
+    ATV: ActionTypeValueEnum<AT> + ActionTypeFor<AT>,
+    O: ActionOrdering<AT, ATV> + Clone,
+{
+    /// Create a new [Program](Program), where the provided `actions`
+    /// will be sorted using the given `ordering`.
+    pub fn new_with_ordering(actions: Vec<Rc<ATV>>, ordering: &O) -> Program<AT, ATV, O> {
+        let mut sorted_actions: Vec<Rc<ATV>> = actions;
+        sorted_actions.sort_by(|a, b| ordering.compare(a, b));
+        Program {
+            actions: sorted_actions,
+            ordering: ordering.clone(),
+            action_type: PhantomData,
+        }
+    }
+
+    /// Create a new [Program](Program) from `actions` which have
+    /// already been sorted using the given `ordering`. The actions are
+    /// not re-sorted, instead an
+    /// [UnsortedActions](AccountingError::UnsortedActions) error is
+    /// returned if they are not in order.
+    pub fn from_sorted(
+        actions: Vec<Rc<ATV>>,
+        ordering: &O,
+    ) -> Result<Program<AT, ATV, O>, AccountingError> {
+        if let Some(index) = actions
+            .windows(2)
+            .position(|pair| ordering.compare(&pair[0], &pair[1]) == Ordering::Greater)
+        {
+            return Err(AccountingError::UnsortedActions(index + 1));
+        }
+
+        Ok(Program {
+            actions,
+            ordering: ordering.clone(),
+            action_type: PhantomData,
+        })
+    }
+
+    /// The [ActionOrdering](ActionOrdering) used to order the actions
+    /// of this program.
+    pub fn ordering(&self) -> &O {
+        &self.ordering
+    }
 
     /// The number of actions in this program.
     pub fn len(&self) -> usize {
@@ -46,37 +107,46 @@ where
     pub fn is_empty(&self) -> bool {
         self.actions.is_empty()
     }
+
+    /// Create an [ActionQuery](ActionQuery) to select actions in this
+    /// program.
+    pub fn query(&self) -> ActionQuery<'_, AT, ATV> {
+        ActionQuery::new(self)
+    }
 }
 
 #[cfg(feature = "serde-support")]
-struct ProgramVisitor<AT, ATV> {
+struct ProgramVisitor<AT, ATV, O> {
     action_type: PhantomData<AT>,
     action_type_value: PhantomData<ATV>,
+    ordering: PhantomData<O>,
 }
 
 #[cfg(feature = "serde-support")]
-impl<AT, ATV> ProgramVisitor<AT, ATV> {
+impl<AT, ATV, O> ProgramVisitor<AT, ATV, O> {
     pub fn new() -> Self {
         Self {
-            action_type: PhantomData::default(),
-            action_type_value: PhantomData::default(),
+            action_type: PhantomData,
+            action_type_value: PhantomData,
+            ordering: PhantomData,
         }
     }
 }
 
 #[cfg(feature = "serde-support")]
-impl<'de, AT, ATV> de::Visitor<'de> for ProgramVisitor<AT, ATV>
+impl<'de, AT, ATV, O> de::Visitor<'de> for ProgramVisitor<AT, ATV, O>
 where
     AT: Ord,
     ATV: Deserialize<'de> + ActionTypeValueEnum<AT> + ActionTypeFor<AT>,
+    O: ActionOrdering<AT, ATV> + Clone + Default,
 {
-    type Value = Program<AT, ATV>;
+    type Value = Program<AT, ATV, O>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         formatter.write_str("Program comprising of a vector of Actions")
     }
 
-    fn visit_seq<S>(self, mut seq: S) -> Result<Program<AT, ATV>, S::Error>
+    fn visit_seq<S>(self, mut seq: S) -> Result<Program<AT, ATV, O>, S::Error>
     where
         S: de::SeqAccess<'de>,
     {
@@ -89,26 +159,30 @@ where
             actions.push(Rc::new(action));
         }
 
-        Ok(Program::new(actions))
+        Ok(Program::new_with_ordering(actions, &O::default()))
     }
 }
 
+/// A [Program](Program) is deserialized from a sequence of actions,
+/// which are sorted using the default value of its
+/// [ActionOrdering](ActionOrdering).
 #[cfg(feature = "serde-support")]
-impl<'de, AT, ATV> Deserialize<'de> for Program<AT, ATV>
+impl<'de, AT, ATV, O> Deserialize<'de> for Program<AT, ATV, O>
 where
     AT: Ord,
     ATV: Deserialize<'de> + ActionTypeValueEnum<AT> + ActionTypeFor<AT>,
+    O: ActionOrdering<AT, ATV> + Clone + Default,
 {
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Program<AT, ATV>, D::Error>
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Program<AT, ATV, O>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_seq(ProgramVisitor::<AT, ATV>::new())
+        deserializer.deserialize_seq(ProgramVisitor::<AT, ATV, O>::new())
     }
 }
 
 #[cfg(feature = "serde-support")]
-impl<AT, ATV> Serialize for Program<AT, ATV>
+impl<AT, ATV, O> Serialize for Program<AT, ATV, O>
 where
     ATV: Serialize,
 {
@@ -132,11 +206,146 @@ pub struct ProgramState<AT = ActionType, ATV = ActionTypeValue> {
     /// list of failed assertions, and associated failed balance
     pub failed_balance_assertions: Vec<FailedBalanceAssertion>,
 
+    /// list of transactions which have been performed (including
+    /// those generated by other actions such as [Pad](crate::Pad)),
+    /// with all their amounts calculated
+    pub journal: Vec<Transaction>,
+
+    /// prices of commodities recorded by [Price](crate::Price) actions
+    pub price_database: PriceDatabase,
+
+    /// the account to post gains (and losses) realized by the
+    /// disposal of [Lot](crate::Lot)s to
+    pub realized_gains_account_id: Option<AccountID>,
+
+    /// list of disposals of [Lot](crate::Lot)s, and their realized gains
+    pub disposals: Vec<Disposal>,
+
+    /// the account to post the residual of a transaction which does
+    /// not sum to zero (such as when converted amounts are rounded)
+    /// to, if the residual is within the `rounding_tolerances`
+    pub rounding_account_id: Option<AccountID>,
+
+    /// the largest residual for each commodity type which is posted
+    /// to the `rounding_account_id`, residuals of commodity types
+    /// without a tolerance are not posted
+    pub rounding_tolerances: HashMap<CommodityTypeID, Decimal>,
+
+    /// how violations of [AccountConstraint](crate::AccountConstraint)s
+    /// are handled
+    pub constraint_mode: ConstraintMode,
+
+    /// list of constraint violations, recorded when the
+    /// `constraint_mode` is [Record](ConstraintMode::Record)
+    pub constraint_violations: Vec<ConstraintViolation>,
+
+    /// if set, the sum of account balances is checked while the
+    /// program is being executed
+    pub integrity_mode: Option<IntegrityMode>,
+
+    /// whether the history of account balances is recorded while
+    /// executing a program, available using
+    /// [balance_on()](ProgramState::balance_on()). This is enabled
+    /// automatically for programs containing an action which
+    /// [needs it](Action::needs_balance_history()), and when
+    /// forecasting.
+    pub record_balance_history: bool,
+
+    /// the balance of each commodity type held by each account at the
+    /// end of each date that it changed
+    balance_history: HashMap<(AccountID, CommodityTypeID), BTreeMap<NaiveDate, Commodity>>,
+
+    /// the actions of the program currently being executed
+    actions: Rc<[Rc<ATV>]>,
+
     /// the index of the currently executing action
     current_action_index: usize,
 
     action_type: PhantomData<AT>,
-    action_type_value: PhantomData<ATV>,
+}
+
+impl<AT, ATV> Clone for ProgramState<AT, ATV> {
+    fn clone(&self) -> Self {
+        ProgramState {
+            account_states: self.account_states.clone(),
+            failed_balance_assertions: self.failed_balance_assertions.clone(),
+            journal: self.journal.clone(),
+            price_database: self.price_database.clone(),
+            realized_gains_account_id: self.realized_gains_account_id,
+            disposals: self.disposals.clone(),
+            rounding_account_id: self.rounding_account_id,
+            rounding_tolerances: self.rounding_tolerances.clone(),
+            constraint_mode: self.constraint_mode,
+            constraint_violations: self.constraint_violations.clone(),
+            integrity_mode: self.integrity_mode.clone(),
+            record_balance_history: self.record_balance_history,
+            balance_history: self.balance_history.clone(),
+            actions: self.actions.clone(),
+            current_action_index: self.current_action_index,
+            action_type: PhantomData,
+        }
+    }
+}
+
+impl<AT, ATV> ProgramState<AT, ATV>
+where
+    AT: Ord,
+    ATV: ActionTypeValueEnum<AT> + ActionTypeFor<AT> + From<Transaction>,
+{
+    /// Execute a given [Program](Program) on a copy of this state,
+    /// producing a [Forecast](Forecast) of account balances. This
+    /// state is not modified.
+    ///
+    /// The transactions generated by the `schedules` are ordered
+    /// among the actions of the `program` using the program's
+    /// [ordering()](Program::ordering()).
+    ///
+    /// Actions dated on or before `today` are actual. Actions in the
+    /// `program` dated after `today`, along with the transactions
+    /// generated by the `schedules` dated after `today`, are
+    /// projected. Nothing after the `horizon` date is performed.
+    /// Scheduled transactions on or before `today` are ignored, as
+    /// they are expected to have already been entered into the
+    /// `program`.
+    pub fn forecast<O>(
+        &self,
+        program: &Program<AT, ATV, O>,
+        schedules: &[Schedule],
+        today: NaiveDate,
+        horizon: NaiveDate,
+    ) -> Result<Forecast, AccountingError>
+    where
+        O: ActionOrdering<AT, ATV> + Clone,
+    {
+        let mut actions: Vec<Rc<ATV>> = program
+            .actions
+            .iter()
+            .filter(|action| action.as_action().date() <= horizon)
+            .cloned()
+            .collect();
+
+        for schedule in schedules {
+            actions.extend(
+                schedule
+                    .transactions_until(horizon)
+                    .into_iter()
+                    .filter(|transaction| transaction.date > today)
+                    .map(|transaction| Rc::new(ATV::from(transaction))),
+            );
+        }
+
+        let forecast_program = Program::new_with_ordering(actions, program.ordering());
+        let mut projected_state = self.clone();
+        projected_state.record_balance_history = true;
+        projected_state.execute_actions(&forecast_program.actions)?;
+
+        Ok(Forecast::from_balance_history(
+            today,
+            horizon,
+            &projected_state.balance_history,
+            &projected_state.account_states,
+        ))
+    }
 }
 
 /// Sum the values in all the accounts into a single
@@ -151,19 +360,19 @@ pub fn sum_account_states(
 ) -> Result<Commodity, AccountingError> {
     let mut sum = Commodity::zero(sum_commodity_type_id);
 
-    for account_state in account_states.values() {
-        let account_amount = if account_state.amount.type_id != sum_commodity_type_id {
+    for amount in account_states.values().flat_map(AccountState::balances) {
+        let account_amount = if amount.type_id != sum_commodity_type_id {
             match exchange_rate {
-                Some(rate) => rate.convert(account_state.amount, sum_commodity_type_id)?,
+                Some(rate) => rate.convert(amount, sum_commodity_type_id)?,
                 None => {
                     return Err(AccountingError::NoExchangeRateSupplied(
-                        account_state.amount,
+                        amount,
                         sum_commodity_type_id,
                     ))
                 }
             }
         } else {
-            account_state.amount
+            amount
         };
 
         sum = sum.add(&account_amount)?;
@@ -194,21 +403,49 @@ where
         ProgramState {
             account_states,
             failed_balance_assertions: Vec::new(),
+            journal: Vec::new(),
+            price_database: PriceDatabase::new(),
+            realized_gains_account_id: None,
+            disposals: Vec::new(),
+            rounding_account_id: None,
+            rounding_tolerances: HashMap::new(),
+            constraint_mode: ConstraintMode::default(),
+            constraint_violations: Vec::new(),
+            integrity_mode: None,
+            record_balance_history: false,
+            balance_history: HashMap::new(),
+            actions: Rc::from(Vec::new()),
             current_action_index: 0,
-            action_type: PhantomData::default(),
-            action_type_value: PhantomData::default(),
+            action_type: PhantomData,
         }
     }
 
     /// Execute a given [Program](Program) to mutate this state.
-    pub fn execute_program(&mut self, program: &Program<AT, ATV>) -> Result<(), AccountingError> {
-        for (index, action) in program.actions.iter().enumerate() {
-            action.as_action().perform(self)?;
+    pub fn execute_program<O>(
+        &mut self,
+        program: &Program<AT, ATV, O>,
+    ) -> Result<(), AccountingError> {
+        self.execute_actions(&program.actions)
+    }
+
+    /// Perform the `actions` (which need to be sorted).
+    fn execute_actions(&mut self, actions: &[Rc<ATV>]) -> Result<(), AccountingError> {
+        self.actions = Rc::from(actions);
+        self.record_balance_history |= actions
+            .iter()
+            .any(|action| action.as_action().needs_balance_history());
+
+        for (index, action) in actions.iter().enumerate() {
             self.current_action_index = index;
+            let action = action.as_action();
+            let journal_len = self.journal.len();
+            action.perform(self)?;
+            self.record_balances(action, journal_len);
+            self.check_integrity(index, action)?;
         }
 
         // TODO: change this to return a list of failed assertions in the error
-        if let Some(failed_assertion) = self.failed_balance_assertions.get(0) {
+        if let Some(failed_assertion) = self.failed_balance_assertions.first() {
             return Err(AccountingError::BalanceAssertionFailed(
                 failed_assertion.clone(),
             ));
@@ -217,6 +454,120 @@ where
         Ok(())
     }
 
+    /// Check the sum of account balances using the `integrity_mode`
+    /// (if there is one) after the `action` at `index` has been
+    /// performed.
+    fn check_integrity(
+        &self,
+        index: usize,
+        action: &dyn Action<AT, ATV>,
+    ) -> Result<(), AccountingError> {
+        let integrity_mode = match &self.integrity_mode {
+            Some(integrity_mode) if integrity_mode.is_due(index, self.actions.len()) => {
+                integrity_mode
+            }
+            _ => return Ok(()),
+        };
+
+        match integrity_mode.unbalanced_sum(&self.account_states)? {
+            Some(sum) => Err(AccountingError::FailedCheckSum(Box::new(CheckSumFailure {
+                sum,
+                action_index: index,
+                action_date: action.date(),
+                action: format!("{:?}", action),
+            }))),
+            None => Ok(()),
+        }
+    }
+
+    /// The index of the action currently being executed within the
+    /// [Program](Program).
+    pub fn current_action_index(&self) -> usize {
+        self.current_action_index
+    }
+
+    /// The actions in the [Program](Program) being executed which
+    /// come after the action currently being executed. This allows
+    /// an action to look ahead in the program.
+    pub fn remaining_actions(&self) -> &[Rc<ATV>] {
+        self.actions
+            .get((self.current_action_index + 1)..)
+            .unwrap_or(&[])
+    }
+
+    /// Perform the remaining actions in the [Program](Program) being
+    /// executed, up until (but not including) the action at
+    /// `end_index`, in order to simulate the balances of the accounts
+    /// with the given `account_ids`, and return the resulting state.
+    /// This state is not modified.
+    ///
+    /// Only the actions which can affect the balances of these
+    /// accounts are performed: those which refer to one of these
+    /// accounts, or to an account referred to by a later action
+    /// which is performed. The simulated state starts with a copy of
+    /// the account states (and their balance history), but not the
+    /// `journal` or the other records of this state.
+    pub fn simulate_until(
+        &self,
+        end_index: usize,
+        account_ids: &HashSet<AccountID>,
+    ) -> Result<ProgramState<AT, ATV>, AccountingError> {
+        let start_index = self.current_action_index + 1;
+        let end_index = end_index.min(self.actions.len());
+
+        let mut relevant_account_ids = account_ids.clone();
+        let mut relevant_indices: Vec<usize> = Vec::new();
+        for index in (start_index..end_index).rev() {
+            let action_account_ids = self.actions[index].as_action().account_ids();
+            if action_account_ids
+                .iter()
+                .any(|account_id| relevant_account_ids.contains(account_id))
+            {
+                relevant_account_ids.extend(action_account_ids);
+                relevant_indices.push(index);
+            }
+        }
+
+        let balance_history = if self.record_balance_history {
+            self.balance_history
+                .iter()
+                .filter(|((account_id, _), _)| relevant_account_ids.contains(account_id))
+                .map(|(key, history)| (*key, history.clone()))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let mut simulated_state = ProgramState {
+            account_states: self.account_states.clone(),
+            failed_balance_assertions: Vec::new(),
+            journal: Vec::new(),
+            price_database: PriceDatabase::default(),
+            realized_gains_account_id: self.realized_gains_account_id,
+            disposals: Vec::new(),
+            rounding_account_id: self.rounding_account_id,
+            rounding_tolerances: self.rounding_tolerances.clone(),
+            constraint_mode: self.constraint_mode,
+            constraint_violations: Vec::new(),
+            integrity_mode: None,
+            record_balance_history: self.record_balance_history,
+            balance_history,
+            actions: self.actions.clone(),
+            current_action_index: self.current_action_index,
+            action_type: PhantomData,
+        };
+
+        for index in relevant_indices.into_iter().rev() {
+            simulated_state.current_action_index = index;
+            let action = simulated_state.actions[index].clone();
+            let journal_len = simulated_state.journal.len();
+            action.as_action().perform(&mut simulated_state)?;
+            simulated_state.record_balances(action.as_action(), journal_len);
+        }
+
+        Ok(simulated_state)
+    }
+
     /// Get the reference to an [Account](Account) using it's [AccountID](AccountID).
     pub fn get_account(&self, account_id: &AccountID) -> Option<&Account> {
         self.get_account_state(account_id)
@@ -242,6 +593,95 @@ where
         self.failed_balance_assertions
             .push(failed_balance_assertion);
     }
+
+    /// Record a performed [Transaction](Transaction) in the `journal`.
+    pub fn record_transaction(&mut self, transaction: Transaction) {
+        self.journal.push(transaction);
+    }
+
+    /// The balance of the commodity type with the id `type_id` held
+    /// by the account with the given id at the end of the given
+    /// `date`, using the history of balances recorded while the
+    /// [Program](Program) is being executed (if
+    /// `record_balance_history` is enabled). Returns `None` if the
+    /// account does not exist.
+    pub fn balance_on(
+        &self,
+        account_id: &AccountID,
+        type_id: CommodityTypeID,
+        date: NaiveDate,
+    ) -> Option<Commodity> {
+        self.get_account_state(account_id)?;
+
+        let recorded = self
+            .balance_history
+            .get(&(*account_id, type_id))
+            .and_then(|history| history.range(..=date).next_back())
+            .map(|(_, balance)| *balance);
+
+        Some(recorded.unwrap_or_else(|| Commodity::zero(type_id)))
+    }
+
+    /// Record the balance of each commodity type held by the
+    /// accounts which the `action` refers to, or which it posted to
+    /// in the transactions it added to the `journal` after
+    /// `journal_len`, if the balance has changed since it was last
+    /// recorded, as its balance at the end of the date of the
+    /// `action`. Does nothing unless `record_balance_history` is
+    /// enabled.
+    fn record_balances(&mut self, action: &dyn Action<AT, ATV>, journal_len: usize) {
+        if !self.record_balance_history {
+            return;
+        }
+
+        let mut keys: Vec<(AccountID, CommodityTypeID)> = Vec::new();
+
+        for account_id in action.account_ids() {
+            if let Some(account_state) = self.account_states.get(&account_id) {
+                for balance in account_state.balances() {
+                    keys.push((account_id, balance.type_id));
+                }
+            }
+        }
+
+        for transaction in &self.journal[journal_len..] {
+            for element in &transaction.elements {
+                if let Some(account_state) = self.account_states.get(&element.account_id) {
+                    for balance in account_state.balances() {
+                        keys.push((element.account_id, balance.type_id));
+                    }
+                }
+
+                // a commodity which is no longer held by the account
+                // is not included in its balances
+                if let Some(amount) = element.amount {
+                    keys.push((element.account_id, amount.type_id));
+                }
+            }
+        }
+
+        let date = action.date();
+
+        for (account_id, type_id) in keys {
+            let balance = match self.account_states.get(&account_id) {
+                Some(account_state) => account_state.balance(type_id),
+                None => continue,
+            };
+
+            let history = self
+                .balance_history
+                .entry((account_id, type_id))
+                .or_default();
+            let changed = match history.values().next_back() {
+                Some(recorded) => *recorded != balance,
+                None => !balance.value.is_zero(),
+            };
+
+            if changed {
+                history.insert(date, balance);
+            }
+        }
+    }
 }
 
 #[cfg(feature = "serde-support")]
@@ -249,8 +689,8 @@ where
 mod tests {
     use super::Program;
     use crate::{
-        Account, AccountID, AccountStatus, ActionTypeValue, BalanceAssertion, EditAccountStatus,
-        Transaction, TransactionElement,
+        Account, AccountID, AccountStatus, ActionType, ActionTypeFor, ActionTypeValue,
+        AsWrittenOrdering, BalanceAssertion, EditAccountStatus, Transaction, TransactionElement,
     };
     use chrono::NaiveDate;
     use commodity::{Commodity, CommodityType, CommodityTypeID};
@@ -371,4 +811,54 @@ mod tests {
 
         insta::assert_json_snapshot!(program);
     }
+
+    #[test]
+    fn program_serde_ordering() {
+        let json = r#"
+[
+    {
+        "type": "Transaction",
+        "date": "2020-01-01",
+        "elements": [
+            {
+                "account_id": "TestAccount1",
+                "amount": {
+                    "value": "-1",
+                    "type_id": "AUD"
+                }
+            },
+            {
+                "account_id": "TestAccount2"
+            }
+        ]
+    },
+    {
+        "type": "BalanceAssertion",
+        "account_id": "TestAccount1",
+        "date": "2020-01-01",
+        "expected_balance": {
+            "value": "-1",
+            "type_id": "AUD"
+        }
+    }
+]"#;
+        let action_types = |actions: &[Rc<ActionTypeValue>]| -> Vec<ActionType> {
+            actions.iter().map(|action| action.action_type()).collect()
+        };
+
+        // balance assertions are moved to the start of the day by the
+        // default ordering
+        let program: Program = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            vec![ActionType::BalanceAssertion, ActionType::Transaction],
+            action_types(&program.actions)
+        );
+
+        let program: Program<ActionType, ActionTypeValue, AsWrittenOrdering> =
+            serde_json::from_str(json).unwrap();
+        assert_eq!(
+            vec![ActionType::Transaction, ActionType::BalanceAssertion],
+            action_types(&program.actions)
+        );
+    }
 }
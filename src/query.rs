@@ -0,0 +1,391 @@
+use super::{account_subtree_ids, Account, AccountID, ActionTypeFor, ActionTypeValueEnum, Program};
+use chrono::NaiveDate;
+use commodity::CommodityTypeID;
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// A query to select the actions of a [Program](Program) which match
+/// all of its filters, created using [Program::query()](Program::query()).
+///
+/// The matching actions are returned as references to those stored in
+/// the program, in the order that they occur in the program.
+///
+/// # Example
+/// ```
+/// use doublecount::{Account, ActionType, ActionTypeValue, Program, Transaction};
+/// use commodity::{Commodity, CommodityType};
+/// use chrono::NaiveDate;
+/// use std::rc::Rc;
+/// use std::str::FromStr;
+///
+/// let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+/// let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+/// let groceries = Rc::from(Account::new_with_id(Some("Groceries"), aud.id, None));
+///
+/// let actions: Vec<Rc<ActionTypeValue>> = vec![
+///     Rc::new(
+///         Transaction::new_simple(
+///             Some("Supermarket"),
+///             NaiveDate::from_ymd_opt(2020, 1, 5).unwrap(),
+///             bank.id,
+///             groceries.id,
+///             Commodity::from_str("45.00 AUD").unwrap(),
+///             None,
+///         )
+///         .with_tag("food")
+///         .into(),
+///     ),
+/// ];
+///
+/// let program = Program::new(actions);
+/// let matches = program
+///     .query()
+///     .action_type(ActionType::Transaction)
+///     .account(groceries.id)
+///     .tag("food")
+///     .description_contains("supermarket")
+///     .actions();
+///
+/// assert_eq!(1, matches.len());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ActionQuery<'a, AT, ATV> {
+    actions: &'a [Rc<ATV>],
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+    action_types: Vec<AT>,
+    account_ids: Option<HashSet<AccountID>>,
+    min_amount: Option<Decimal>,
+    max_amount: Option<Decimal>,
+    commodity_type_id: Option<CommodityTypeID>,
+    tags: Vec<String>,
+    description: Option<String>,
+}
+
+impl<'a, AT, ATV> ActionQuery<'a, AT, ATV>
+where
+    AT: PartialEq,
+    ATV: ActionTypeValueEnum<AT> + ActionTypeFor<AT>,
+{
+    /// Create a new [ActionQuery](ActionQuery) which matches every
+    /// action in the `program`.
+    pub fn new<O>(program: &'a Program<AT, ATV, O>) -> Self {
+        ActionQuery {
+            actions: &program.actions,
+            start_date: None,
+            end_date: None,
+            action_types: Vec::new(),
+            account_ids: None,
+            min_amount: None,
+            max_amount: None,
+            commodity_type_id: None,
+            tags: Vec::new(),
+            description: None,
+        }
+    }
+
+    /// Only match actions which occur on or after the `start_date`.
+    pub fn start_date(mut self, start_date: NaiveDate) -> Self {
+        self.start_date = Some(start_date);
+        self
+    }
+
+    /// Only match actions which occur on or before the `end_date`.
+    pub fn end_date(mut self, end_date: NaiveDate) -> Self {
+        self.end_date = Some(end_date);
+        self
+    }
+
+    /// Only match actions which occur between the `start_date` and
+    /// the `end_date` (both inclusive).
+    pub fn date_range(self, start_date: NaiveDate, end_date: NaiveDate) -> Self {
+        self.start_date(start_date).end_date(end_date)
+    }
+
+    /// Match actions of the given `action_type`. This can be called
+    /// multiple times to match actions of any of the given types.
+    pub fn action_type(mut self, action_type: AT) -> Self {
+        self.action_types.push(action_type);
+        self
+    }
+
+    /// Match actions which refer to the account with the given
+    /// `account_id`. This can be called multiple times to match
+    /// actions which refer to any of the given accounts.
+    pub fn account(mut self, account_id: AccountID) -> Self {
+        self.account_ids
+            .get_or_insert_with(HashSet::new)
+            .insert(account_id);
+        self
+    }
+
+    /// Match actions which refer to the account with the given
+    /// `account_id`, or any of its descendants (according to the
+    /// [parent_id](Account::parent_id) of the provided `accounts`).
+    pub fn account_subtree(mut self, account_id: AccountID, accounts: &[Rc<Account>]) -> Self {
+        self.account_ids
+            .get_or_insert_with(HashSet::new)
+            .extend(account_subtree_ids(
+                account_id,
+                accounts.iter().map(|account| account.as_ref()),
+            ));
+        self
+    }
+
+    /// Only match actions with an amount whose absolute value is
+    /// greater than or equal to `min_amount`.
+    pub fn min_amount(mut self, min_amount: Decimal) -> Self {
+        self.min_amount = Some(min_amount);
+        self
+    }
+
+    /// Only match actions with an amount whose absolute value is less
+    /// than or equal to `max_amount`.
+    pub fn max_amount(mut self, max_amount: Decimal) -> Self {
+        self.max_amount = Some(max_amount);
+        self
+    }
+
+    /// Only match actions with an amount whose absolute value is
+    /// between `min_amount` and `max_amount` (both inclusive).
+    pub fn amount_range(self, min_amount: Decimal, max_amount: Decimal) -> Self {
+        self.min_amount(min_amount).max_amount(max_amount)
+    }
+
+    /// Only match actions with an amount of the commodity type with
+    /// the given `commodity_type_id`. When combined with an amount
+    /// filter, the same amount needs to match both.
+    pub fn commodity(mut self, commodity_type_id: CommodityTypeID) -> Self {
+        self.commodity_type_id = Some(commodity_type_id);
+        self
+    }
+
+    /// Only match [Transaction](crate::Transaction)s with the given
+    /// `tag`. This can be called multiple times to match transactions
+    /// which have all of the given tags.
+    pub fn tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Only match [Transaction](crate::Transaction)s with a
+    /// description which contains the given `text`, ignoring case.
+    pub fn description_contains<S: Into<String>>(mut self, text: S) -> Self {
+        self.description = Some(text.into().to_lowercase());
+        self
+    }
+
+    /// Whether the `action` matches all the filters of this query.
+    pub fn matches(&self, action: &ATV) -> bool {
+        let action_trait = action.as_action();
+        let date = action_trait.date();
+
+        if self
+            .start_date
+            .map_or(false, |start_date| date < start_date)
+            || self.end_date.map_or(false, |end_date| date > end_date)
+        {
+            return false;
+        }
+
+        if !self.action_types.is_empty() && !self.action_types.contains(&action.action_type()) {
+            return false;
+        }
+
+        if let Some(account_ids) = &self.account_ids {
+            if !action_trait
+                .account_ids()
+                .iter()
+                .any(|account_id| account_ids.contains(account_id))
+            {
+                return false;
+            }
+        }
+
+        if self.commodity_type_id.is_some()
+            || self.min_amount.is_some()
+            || self.max_amount.is_some()
+        {
+            let amount_matches = action_trait.amounts().iter().any(|amount| {
+                let value = amount.value.abs();
+                self.commodity_type_id
+                    .map_or(true, |type_id| amount.type_id == type_id)
+                    && self
+                        .min_amount
+                        .map_or(true, |min_amount| value >= min_amount)
+                    && self
+                        .max_amount
+                        .map_or(true, |max_amount| value <= max_amount)
+            });
+
+            if !amount_matches {
+                return false;
+            }
+        }
+
+        if !self.tags.is_empty() || self.description.is_some() {
+            let transaction = match action_trait.as_transaction() {
+                Some(transaction) => transaction,
+                None => return false,
+            };
+
+            if !self.tags.iter().all(|tag| transaction.has_tag(tag)) {
+                return false;
+            }
+
+            if let Some(text) = &self.description {
+                if !transaction
+                    .description
+                    .as_ref()
+                    .map_or(false, |description| {
+                        description.to_lowercase().contains(text)
+                    })
+                {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// An iterator over the actions in the program which match this
+    /// query.
+    pub fn iter(&self) -> impl Iterator<Item = &'a Rc<ATV>> + '_ {
+        self.actions
+            .iter()
+            .filter(move |action| self.matches(action))
+    }
+
+    /// The actions in the program which match this query.
+    pub fn actions(&self) -> Vec<&'a Rc<ATV>> {
+        self.iter().collect()
+    }
+
+    /// The number of actions in the program which match this query.
+    pub fn count(&self) -> usize {
+        self.iter().count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Account, ActionType, ActionTypeValue, BalanceAssertion, Price, Program, Transaction,
+    };
+    use chrono::NaiveDate;
+    use commodity::{Commodity, CommodityType};
+    use rust_decimal::Decimal;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    #[test]
+    fn query() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let aud = Rc::from(CommodityType::from_str("AUD", "Australian Dollar").unwrap());
+        let usd = Rc::from(CommodityType::from_str("USD", "US Dollar").unwrap());
+
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), aud.id, None));
+        let expenses = Rc::from(Account::new_with_id(Some("Expenses"), aud.id, None));
+        let food =
+            Rc::from(Account::new_with_id(Some("Food"), aud.id, None).with_parent_id(expenses.id));
+        let groceries =
+            Rc::from(Account::new_with_id(Some("Groceries"), aud.id, None).with_parent_id(food.id));
+        let rent =
+            Rc::from(Account::new_with_id(Some("Rent"), aud.id, None).with_parent_id(expenses.id));
+        let accounts = vec![
+            bank.clone(),
+            expenses.clone(),
+            food.clone(),
+            groceries.clone(),
+            rent.clone(),
+        ];
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(
+                Transaction::new_simple(
+                    Some("Weekly Groceries"),
+                    date(2020, 1, 3),
+                    bank.id,
+                    groceries.id,
+                    Commodity::from_str("120 AUD").unwrap(),
+                    None,
+                )
+                .with_tag("household")
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new_simple(
+                    Some("Rent for January"),
+                    date(2020, 1, 1),
+                    bank.id,
+                    rent.id,
+                    Commodity::from_str("1500 AUD").unwrap(),
+                    None,
+                )
+                .with_tag("household")
+                .with_tag("fixed")
+                .into(),
+            ),
+            Rc::new(
+                BalanceAssertion::new(
+                    bank.id,
+                    date(2020, 2, 1),
+                    Commodity::from_str("-1620 AUD").unwrap(),
+                )
+                .into(),
+            ),
+            Rc::new(
+                Price::new(
+                    usd.id,
+                    Commodity::from_str("1.45 AUD").unwrap(),
+                    date(2020, 1, 15),
+                )
+                .into(),
+            ),
+        ];
+
+        let program = Program::new(actions);
+
+        assert_eq!(4, program.query().count());
+        assert_eq!(
+            2,
+            program.query().action_type(ActionType::Transaction).count()
+        );
+        assert_eq!(
+            2,
+            program
+                .query()
+                .date_range(date(2020, 1, 2), date(2020, 1, 31))
+                .count()
+        );
+        assert_eq!(3, program.query().account(bank.id).count());
+        assert_eq!(
+            2,
+            program
+                .query()
+                .account_subtree(expenses.id, &accounts)
+                .count()
+        );
+        assert_eq!(
+            1,
+            program.query().account_subtree(food.id, &accounts).count()
+        );
+        assert_eq!(
+            2,
+            program
+                .query()
+                .amount_range(Decimal::from(100), Decimal::from(1500))
+                .commodity(aud.id)
+                .count()
+        );
+        assert_eq!(2, program.query().max_amount(Decimal::from(200)).count());
+        assert_eq!(2, program.query().tag("household").count());
+        assert_eq!(1, program.query().tag("household").tag("fixed").count());
+
+        let matches = program.query().description_contains("rent").actions();
+        assert_eq!(1, matches.len());
+        assert!(Rc::ptr_eq(&program.actions[0], matches[0]));
+    }
+}
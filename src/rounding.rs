@@ -0,0 +1,121 @@
+use commodity::Commodity;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+#[cfg(feature = "serde-support")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The policy used to round calculated amounts (such as interest) to
+/// a whole number of the smallest unit of a commodity.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RoundingPolicy {
+    /// The number of decimal places to round to.
+    pub decimal_places: u32,
+    /// The strategy used to round values.
+    #[cfg_attr(feature = "serde-support", serde(with = "strategy_serde"))]
+    pub strategy: RoundingStrategy,
+}
+
+impl RoundingPolicy {
+    /// Create a new [RoundingPolicy](RoundingPolicy).
+    pub fn new(decimal_places: u32, strategy: RoundingStrategy) -> RoundingPolicy {
+        RoundingPolicy {
+            decimal_places,
+            strategy,
+        }
+    }
+
+    /// Round a `value` using this policy.
+    pub fn round(&self, value: Decimal) -> Decimal {
+        value.round_dp_with_strategy(self.decimal_places, self.strategy)
+    }
+
+    /// Round the value of a `commodity` using this policy.
+    pub fn round_commodity(&self, commodity: Commodity) -> Commodity {
+        Commodity::new(self.round(commodity.value), commodity.type_id)
+    }
+
+    /// Returns true if this is the default policy.
+    pub fn is_default(&self) -> bool {
+        *self == RoundingPolicy::default()
+    }
+}
+
+/// Serialize a [RoundingStrategy](RoundingStrategy) (which does not
+/// implement serde's traits) using the name of the strategy.
+#[cfg(feature = "serde-support")]
+mod strategy_serde {
+    use super::{Deserialize, Deserializer, RoundingStrategy, Serialize, Serializer};
+
+    /// A mirror of [RoundingStrategy](RoundingStrategy), excluding its
+    /// deprecated variants.
+    #[derive(Serialize, Deserialize)]
+    enum Strategy {
+        MidpointNearestEven,
+        MidpointAwayFromZero,
+        MidpointTowardZero,
+        ToZero,
+        AwayFromZero,
+        ToNegativeInfinity,
+        ToPositiveInfinity,
+    }
+
+    #[allow(deprecated)]
+    impl From<RoundingStrategy> for Strategy {
+        fn from(strategy: RoundingStrategy) -> Self {
+            match strategy {
+                RoundingStrategy::MidpointNearestEven | RoundingStrategy::BankersRounding => {
+                    Strategy::MidpointNearestEven
+                }
+                RoundingStrategy::MidpointAwayFromZero | RoundingStrategy::RoundHalfUp => {
+                    Strategy::MidpointAwayFromZero
+                }
+                RoundingStrategy::MidpointTowardZero | RoundingStrategy::RoundHalfDown => {
+                    Strategy::MidpointTowardZero
+                }
+                RoundingStrategy::ToZero | RoundingStrategy::RoundDown => Strategy::ToZero,
+                RoundingStrategy::AwayFromZero | RoundingStrategy::RoundUp => {
+                    Strategy::AwayFromZero
+                }
+                RoundingStrategy::ToNegativeInfinity => Strategy::ToNegativeInfinity,
+                RoundingStrategy::ToPositiveInfinity => Strategy::ToPositiveInfinity,
+            }
+        }
+    }
+
+    impl From<Strategy> for RoundingStrategy {
+        fn from(strategy: Strategy) -> Self {
+            match strategy {
+                Strategy::MidpointNearestEven => RoundingStrategy::MidpointNearestEven,
+                Strategy::MidpointAwayFromZero => RoundingStrategy::MidpointAwayFromZero,
+                Strategy::MidpointTowardZero => RoundingStrategy::MidpointTowardZero,
+                Strategy::ToZero => RoundingStrategy::ToZero,
+                Strategy::AwayFromZero => RoundingStrategy::AwayFromZero,
+                Strategy::ToNegativeInfinity => RoundingStrategy::ToNegativeInfinity,
+                Strategy::ToPositiveInfinity => RoundingStrategy::ToPositiveInfinity,
+            }
+        }
+    }
+
+    pub fn serialize<S>(strategy: &RoundingStrategy, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Strategy::from(*strategy).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<RoundingStrategy, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Strategy::deserialize(deserializer).map(RoundingStrategy::from)
+    }
+}
+
+impl Default for RoundingPolicy {
+    /// Round to 2 decimal places, with midpoints rounded away from
+    /// zero.
+    fn default() -> Self {
+        RoundingPolicy::new(2, RoundingStrategy::MidpointAwayFromZero)
+    }
+}
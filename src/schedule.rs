@@ -0,0 +1,378 @@
+use super::{AccountingError, Transaction};
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use rust_decimal::Decimal;
+use std::rc::Rc;
+
+#[cfg(feature = "serde-support")]
+use serde::{Deserialize, Serialize};
+
+/// Something which generates a series of [Transaction](Transaction)s,
+/// such as a [Schedule](Schedule), to be included in a
+/// [Program](crate::Program).
+pub trait TransactionGenerator {
+    /// Generate the transactions.
+    fn generate(&self) -> Result<Vec<Transaction>, AccountingError>;
+
+    /// Generate the transactions, wrapped as action type values
+    /// ready to be supplied to [Program::new()](crate::Program::new()).
+    fn actions<ATV>(&self) -> Result<Vec<Rc<ATV>>, AccountingError>
+    where
+        Self: Sized,
+        ATV: From<Transaction>,
+    {
+        Ok(self
+            .generate()?
+            .into_iter()
+            .map(|transaction| Rc::new(ATV::from(transaction)))
+            .collect())
+    }
+}
+
+/// The rule used by a [Schedule](Schedule) to determine the dates
+/// that it occurs on.
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Recurrence {
+    /// Every day.
+    Daily,
+    /// Every 7 days.
+    Weekly,
+    /// The same day of every month as the start date. If the month
+    /// is too short, the last day of the month is used instead.
+    Monthly,
+    /// The same day of the month as the start date, every `n`
+    /// months. `n` needs to be greater than zero.
+    EveryNMonths(u32),
+    /// The last weekday (Monday to Friday) of every month, starting
+    /// from the month of the start date.
+    LastBusinessDay,
+}
+
+impl Recurrence {
+    /// An (infinite) iterator over the dates that this recurrence
+    /// occurs on, beginning at the `start_date`.
+    pub fn dates(&self, start_date: NaiveDate) -> ScheduleDates {
+        ScheduleDates {
+            recurrence: *self,
+            start_date,
+            end_date: None,
+            count: None,
+            index: 0,
+            emitted: 0,
+        }
+    }
+
+    /// The (approximate) number of times that this recurrence occurs
+    /// in a year. This is zero for an invalid
+    /// [EveryNMonths](Recurrence::EveryNMonths) with `n` of `0`.
+    pub fn periods_per_year(&self) -> Decimal {
+        match self {
+            Recurrence::Daily => Decimal::new(365, 0),
+            Recurrence::Weekly => Decimal::new(52, 0),
+            Recurrence::Monthly | Recurrence::LastBusinessDay => Decimal::new(12, 0),
+            Recurrence::EveryNMonths(0) => Decimal::ZERO,
+            Recurrence::EveryNMonths(n) => Decimal::new(12, 0) / Decimal::from(*n),
+        }
+    }
+
+    /// The date of the occurrence with the given `index` (the first
+    /// occurrence is `0`) for a schedule starting on `start_date`.
+    /// This may be before the `start_date` for
+    /// [LastBusinessDay](Recurrence::LastBusinessDay).
+    fn occurrence(&self, start_date: NaiveDate, index: u32) -> Option<NaiveDate> {
+        match self {
+            Recurrence::Daily => start_date.checked_add_signed(Duration::days(index.into())),
+            Recurrence::Weekly => start_date.checked_add_signed(Duration::weeks(index.into())),
+            Recurrence::Monthly => start_date.checked_add_months(Months::new(index)),
+            Recurrence::EveryNMonths(n) => {
+                if *n == 0 {
+                    return None;
+                }
+                start_date.checked_add_months(Months::new(index.checked_mul(*n)?))
+            }
+            Recurrence::LastBusinessDay => {
+                let month_start = start_date
+                    .with_day(1)?
+                    .checked_add_months(Months::new(index))?;
+                let mut date = month_start.checked_add_months(Months::new(1))?.pred_opt()?;
+                while date.weekday() == Weekday::Sat || date.weekday() == Weekday::Sun {
+                    date = date.pred_opt()?;
+                }
+                Some(date)
+            }
+        }
+    }
+}
+
+/// A schedule for a recurring [Transaction](Transaction) (such as
+/// rent, a salary or a subscription), which is expanded from a
+/// `template` into a concrete transaction on each date that the
+/// `recurrence` occurs, beginning at the `start_date`.
+///
+/// The schedule ends after the `end_date` (inclusive), or once
+/// `count` transactions have been generated, whichever comes first.
+///
+/// # Example
+/// ```
+/// use doublecount::{Account, ActionTypeValue, Program, Recurrence, Schedule, Transaction, TransactionGenerator};
+/// use commodity::{Commodity, CommodityTypeID};
+/// use chrono::NaiveDate;
+/// use std::rc::Rc;
+/// use std::str::FromStr;
+///
+/// let usd = CommodityTypeID::from_str("USD").unwrap();
+/// let bank = Account::new_with_id(Some("Bank"), usd, None);
+/// let rent = Account::new_with_id(Some("Rent"), usd, None);
+///
+/// let template = Transaction::new_simple(
+///     Some("Rent"),
+///     NaiveDate::from_ymd_opt(2020, 1, 31).unwrap(),
+///     bank.id,
+///     rent.id,
+///     Commodity::from_str("1000 USD").unwrap(),
+///     None,
+/// );
+///
+/// let schedule = Schedule::new(template, Recurrence::Monthly).with_count(3);
+///
+/// let dates: Vec<NaiveDate> = schedule.dates().collect();
+/// assert_eq!(
+///     vec![
+///         NaiveDate::from_ymd_opt(2020, 1, 31).unwrap(),
+///         NaiveDate::from_ymd_opt(2020, 2, 29).unwrap(),
+///         NaiveDate::from_ymd_opt(2020, 3, 31).unwrap(),
+///     ],
+///     dates
+/// );
+///
+/// let actions: Vec<Rc<ActionTypeValue>> = schedule.actions().unwrap();
+/// let program = Program::new(actions);
+/// assert_eq!(3, program.len());
+/// ```
+#[cfg_attr(feature = "serde-support", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    /// The transaction to repeat. Its `date` is replaced with the
+    /// date of each occurrence.
+    pub template: Transaction,
+    /// The rule used to determine the dates of each occurrence.
+    pub recurrence: Recurrence,
+    /// The date that the schedule begins.
+    pub start_date: NaiveDate,
+    /// The last date (inclusive) that the schedule can occur on.
+    pub end_date: Option<NaiveDate>,
+    /// The maximum number of times that the schedule occurs.
+    pub count: Option<usize>,
+}
+
+impl Schedule {
+    /// Create a new [Schedule](Schedule) for the `template`
+    /// transaction, starting from the date of the `template`, with
+    /// no end date or count.
+    pub fn new(template: Transaction, recurrence: Recurrence) -> Schedule {
+        Schedule {
+            start_date: template.date,
+            template,
+            recurrence,
+            end_date: None,
+            count: None,
+        }
+    }
+
+    /// Set the date that the schedule begins.
+    pub fn with_start_date(mut self, start_date: NaiveDate) -> Schedule {
+        self.start_date = start_date;
+        self
+    }
+
+    /// Set the last date (inclusive) that the schedule can occur on.
+    pub fn with_end_date(mut self, end_date: NaiveDate) -> Schedule {
+        self.end_date = Some(end_date);
+        self
+    }
+
+    /// Set the maximum number of times that the schedule occurs.
+    pub fn with_count(mut self, count: usize) -> Schedule {
+        self.count = Some(count);
+        self
+    }
+
+    /// Returns true if the schedule has either an `end_date` or a
+    /// `count`.
+    pub fn is_bounded(&self) -> bool {
+        self.end_date.is_some() || self.count.is_some()
+    }
+
+    /// An iterator over the dates that this schedule occurs on. This
+    /// iterator is infinite if the schedule is not
+    /// [bounded](Schedule::is_bounded()).
+    pub fn dates(&self) -> ScheduleDates {
+        ScheduleDates {
+            end_date: self.end_date,
+            count: self.count,
+            ..self.recurrence.dates(self.start_date)
+        }
+    }
+
+    /// Create the concrete transaction for an occurrence on the
+    /// given `date`.
+    pub fn transaction(&self, date: NaiveDate) -> Transaction {
+        let mut transaction = self.template.clone();
+        transaction.date = date;
+        transaction
+    }
+
+    /// Generate the transactions which occur on or before the given
+    /// `date`. This can be used with schedules which are not
+    /// [bounded](Schedule::is_bounded()).
+    pub fn transactions_until(&self, date: NaiveDate) -> Vec<Transaction> {
+        self.dates()
+            .take_while(|occurrence| *occurrence <= date)
+            .map(|occurrence| self.transaction(occurrence))
+            .collect()
+    }
+}
+
+impl TransactionGenerator for Schedule {
+    /// Generate all the transactions in this schedule. Returns an
+    /// [UnboundedSchedule](AccountingError::UnboundedSchedule) error if
+    /// the schedule is not [bounded](Schedule::is_bounded()).
+    fn generate(&self) -> Result<Vec<Transaction>, AccountingError> {
+        if !self.is_bounded() {
+            return Err(AccountingError::UnboundedSchedule);
+        }
+
+        Ok(self
+            .dates()
+            .map(|occurrence| self.transaction(occurrence))
+            .collect())
+    }
+}
+
+/// An iterator over the dates that a [Schedule](Schedule) or a
+/// [Recurrence](Recurrence) occurs on, created using
+/// [Schedule::dates()](Schedule::dates()) or
+/// [Recurrence::dates()](Recurrence::dates()).
+#[derive(Debug, Clone)]
+pub struct ScheduleDates {
+    recurrence: Recurrence,
+    start_date: NaiveDate,
+    end_date: Option<NaiveDate>,
+    count: Option<usize>,
+    index: u32,
+    emitted: usize,
+}
+
+impl Iterator for ScheduleDates {
+    type Item = NaiveDate;
+
+    fn next(&mut self) -> Option<NaiveDate> {
+        if let Some(count) = self.count {
+            if self.emitted >= count {
+                return None;
+            }
+        }
+
+        loop {
+            let date = self.recurrence.occurrence(self.start_date, self.index)?;
+            self.index = self.index.checked_add(1)?;
+
+            if let Some(end_date) = self.end_date {
+                if date > end_date {
+                    return None;
+                }
+            }
+
+            if date >= self.start_date {
+                self.emitted += 1;
+                return Some(date);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recurrence, Schedule, TransactionGenerator};
+    use crate::{AccountID, AccountingError, Transaction};
+    use chrono::NaiveDate;
+    use commodity::Commodity;
+    use std::str::FromStr;
+
+    fn template(date: NaiveDate) -> Transaction {
+        Transaction::new_simple(
+            Some("Salary"),
+            date,
+            AccountID::from("Income").unwrap(),
+            AccountID::from("Bank").unwrap(),
+            Commodity::from_str("5000 USD").unwrap(),
+            None,
+        )
+    }
+
+    #[test]
+    fn recurrence() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let dates = |schedule: Schedule| schedule.dates().collect::<Vec<NaiveDate>>();
+
+        assert_eq!(
+            vec![date(2020, 1, 30), date(2020, 2, 6), date(2020, 2, 13)],
+            dates(
+                Schedule::new(template(date(2020, 1, 30)), Recurrence::Weekly)
+                    .with_end_date(date(2020, 2, 19))
+            )
+        );
+        assert_eq!(
+            vec![date(2020, 1, 15), date(2020, 4, 15), date(2020, 7, 15)],
+            dates(
+                Schedule::new(template(date(2020, 1, 15)), Recurrence::EveryNMonths(3))
+                    .with_count(3)
+            )
+        );
+        // January 31st 2020 is a Friday, February 29th 2020 is a
+        // Saturday, and May 31st 2020 is a Sunday.
+        assert_eq!(
+            vec![
+                date(2020, 2, 28),
+                date(2020, 3, 31),
+                date(2020, 4, 30),
+                date(2020, 5, 29)
+            ],
+            dates(
+                Schedule::new(template(date(2020, 2, 1)), Recurrence::LastBusinessDay)
+                    .with_end_date(date(2020, 5, 31))
+            )
+        );
+        assert_eq!(
+            vec![date(2020, 3, 31)],
+            dates(
+                Schedule::new(template(date(2020, 3, 31)), Recurrence::LastBusinessDay)
+                    .with_count(1)
+            )
+        );
+    }
+
+    #[test]
+    fn generate() {
+        let start = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let schedule = Schedule::new(template(start), Recurrence::Daily);
+
+        assert!(matches!(
+            schedule.generate(),
+            Err(AccountingError::UnboundedSchedule)
+        ));
+
+        let until = NaiveDate::from_ymd_opt(2020, 1, 10).unwrap();
+        let transactions = schedule.transactions_until(until);
+        assert_eq!(10, transactions.len());
+        assert_eq!(until, transactions[9].date);
+        assert_eq!(template(start).elements, transactions[9].elements);
+
+        let transactions = schedule
+            .with_end_date(until)
+            .with_count(5)
+            .generate()
+            .unwrap();
+        assert_eq!(5, transactions.len());
+    }
+}
@@ -0,0 +1,618 @@
+use super::{AccountID, AccountingError, PathPreference, PriceLookup, ProgramState, RateGraph};
+use chrono::NaiveDate;
+use commodity::{Commodity, CommodityTypeID};
+use std::collections::HashMap;
+
+/// The valuation of a single commodity type held by an
+/// [Account](crate::Account) within a
+/// [MarketValueReport](MarketValueReport).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountValuation {
+    /// The id of the account being valued.
+    pub account_id: AccountID,
+    /// The balance of the account on the date of the report.
+    pub balance: Commodity,
+    /// The value of the balance at the prices effective when it was
+    /// acquired, using the average cost of the balance (or the cost
+    /// basis of its lots if the account tracks lots).
+    pub book_value: Commodity,
+    /// The value of the balance at the prices effective on the date
+    /// of the report.
+    pub market_value: Commodity,
+    /// The difference between the `market_value` and the
+    /// `book_value`. A negative value represents an unrealized loss.
+    pub unrealized_gain: Commodity,
+}
+
+/// A report of the market value, book value and unrealized gains or
+/// losses for every [Account](crate::Account) holding a commodity
+/// other than the reporting commodity, on a given date. An
+/// [inventory](crate::Account::inventory) account has a valuation for
+/// each commodity type it holds (other than the reporting commodity).
+///
+/// The report is derived from the `journal` and the `price_database`
+/// of an executed [ProgramState](ProgramState). Book values are
+/// calculated using the average cost of each account's balance,
+/// valued using the `cost` of each
+/// [TransactionElement](crate::TransactionElement) if it is known,
+/// otherwise the prices effective on the date of each transaction.
+///
+/// The book values of accounts which track lots (with a
+/// [booking_method](crate::Account::booking_method)) are instead the
+/// cost basis of the lots held on the date of the report, so that
+/// they are consistent with the realized gains of the
+/// [disposals](ProgramState::disposals). Lots which were transferred
+/// to another account after the date of the report are valued in the
+/// account they were transferred to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketValueReport {
+    /// The date of the report.
+    pub date: NaiveDate,
+    /// The id of the commodity type used to value the accounts.
+    pub reporting_commodity_type_id: CommodityTypeID,
+    /// The valuation of each account, ordered by account id and then
+    /// by the id of the commodity type held.
+    pub accounts: Vec<AccountValuation>,
+    /// The sum of the book values of all the accounts.
+    pub total_book_value: Commodity,
+    /// The sum of the market values of all the accounts.
+    pub total_market_value: Commodity,
+    /// The sum of the unrealized gains of all the accounts.
+    pub total_unrealized_gain: Commodity,
+}
+
+impl MarketValueReport {
+    /// Create a new [MarketValueReport](MarketValueReport) on the
+    /// given `date` from an executed `program_state`, valuing
+    /// accounts in the commodity type `reporting_commodity_type_id`.
+    ///
+    /// Prices are looked up in the state's
+    /// [PriceDatabase](crate::PriceDatabase) using `lookup`, and may be
+    /// converted via other commodity types as described by
+    /// [RateGraph](RateGraph).
+    pub fn new<AT, ATV>(
+        program_state: &ProgramState<AT, ATV>,
+        reporting_commodity_type_id: CommodityTypeID,
+        date: NaiveDate,
+        lookup: PriceLookup,
+    ) -> Result<MarketValueReport, AccountingError> {
+        let mut valuer = Valuer::new(program_state, reporting_commodity_type_id, lookup);
+
+        // (balance, book value) for each commodity type held by each
+        // account
+        let mut holdings: HashMap<(AccountID, CommodityTypeID), (Commodity, Commodity)> =
+            HashMap::new();
+        let zero_holding = |type_id| {
+            (
+                Commodity::zero(type_id),
+                Commodity::zero(reporting_commodity_type_id),
+            )
+        };
+
+        for account_state in program_state.account_states.values() {
+            for balance in account_state.balances() {
+                if balance.type_id != reporting_commodity_type_id {
+                    holdings.insert(
+                        (account_state.account.id, balance.type_id),
+                        zero_holding(balance.type_id),
+                    );
+                }
+            }
+        }
+
+        for transaction in program_state
+            .journal
+            .iter()
+            .filter(|transaction| transaction.date <= date)
+        {
+            for element in &transaction.elements {
+                let amount = match element.amount {
+                    Some(amount) => amount,
+                    None => continue,
+                };
+
+                let key = (element.account_id, amount.type_id);
+                if amount.type_id != reporting_commodity_type_id && !holdings.contains_key(&key) {
+                    // an inventory account may have held a commodity type
+                    // on the date of the report which it no longer holds
+                    let inventory = program_state
+                        .account_states
+                        .get(&element.account_id)
+                        .map_or(false, |state| state.account.inventory);
+                    if inventory {
+                        holdings.insert(key, zero_holding(amount.type_id));
+                    }
+                }
+
+                let (balance, book_value) = match holdings.get_mut(&key) {
+                    Some(holding) => holding,
+                    None => continue,
+                };
+
+                let new_balance = balance.add(&amount)?;
+
+                if balance.value.is_zero()
+                    || balance.value.is_sign_negative() == amount.value.is_sign_negative()
+                {
+                    // increasing the size of the holding, at cost if it is known
+                    let cost = match element.cost {
+                        Some(cost) => valuer.value(
+                            Commodity::new(amount.value * cost.value, cost.type_id),
+                            transaction.date,
+                        )?,
+                        None => valuer.value(amount, transaction.date)?,
+                    };
+                    *book_value = book_value.add(&cost)?;
+                } else if new_balance.value.is_zero()
+                    || new_balance.value.is_sign_negative() != balance.value.is_sign_negative()
+                {
+                    // the holding is closed (and possibly reversed)
+                    *book_value = valuer.value(new_balance, transaction.date)?;
+                } else {
+                    // reducing the size of the holding at average cost
+                    let remaining = new_balance.value / balance.value;
+                    *book_value = Commodity::new(book_value.value * remaining, book_value.type_id);
+                }
+
+                *balance = new_balance;
+            }
+        }
+
+        // the lots held on the date of the report are the lots
+        // remaining at the end of the program, and those disposed of
+        // after the date
+        for ((account_id, type_id), (_, book_value)) in holdings.iter_mut() {
+            let account_state = match program_state.account_states.get(account_id) {
+                Some(state) if state.account.booking_method.is_some() => state,
+                _ => continue,
+            };
+
+            let mut lots_book_value = Commodity::zero(reporting_commodity_type_id);
+            for lot in account_state
+                .lots
+                .iter()
+                .filter(|lot| lot.quantity.type_id == *type_id && lot.acquisition_date <= date)
+            {
+                let lot_value = valuer.value(lot.cost_basis(), lot.acquisition_date)?;
+                lots_book_value = lots_book_value.add(&lot_value)?;
+            }
+            for disposal in program_state.disposals.iter().filter(|disposal| {
+                disposal.account_id == *account_id
+                    && disposal.quantity.type_id == *type_id
+                    && disposal.acquisition_date <= date
+                    && disposal.disposal_date > date
+            }) {
+                let lot_value = valuer.value(disposal.cost_basis, disposal.acquisition_date)?;
+                lots_book_value = lots_book_value.add(&lot_value)?;
+            }
+
+            *book_value = lots_book_value;
+        }
+
+        let mut keys: Vec<(AccountID, CommodityTypeID)> = holdings.keys().cloned().collect();
+        keys.sort();
+
+        let mut accounts = Vec::with_capacity(keys.len());
+        let mut total_book_value = Commodity::zero(reporting_commodity_type_id);
+        let mut total_market_value = Commodity::zero(reporting_commodity_type_id);
+
+        for key in keys {
+            let (account_id, _) = key;
+            let (balance, book_value) = holdings[&key];
+            let market_value = valuer.value(balance, date)?;
+            let unrealized_gain = market_value.sub(&book_value)?;
+
+            total_book_value = total_book_value.add(&book_value)?;
+            total_market_value = total_market_value.add(&market_value)?;
+
+            accounts.push(AccountValuation {
+                account_id,
+                balance,
+                book_value,
+                market_value,
+                unrealized_gain,
+            });
+        }
+
+        Ok(MarketValueReport {
+            date,
+            reporting_commodity_type_id,
+            accounts,
+            total_book_value,
+            total_unrealized_gain: total_market_value.sub(&total_book_value)?,
+            total_market_value,
+        })
+    }
+
+    /// Get the valuation for the account with the given id. For an
+    /// [inventory](crate::Account::inventory) account this is the
+    /// valuation of the first commodity type it holds, use
+    /// [get_holding()](MarketValueReport::get_holding()) instead.
+    pub fn get_account(&self, account_id: &AccountID) -> Option<&AccountValuation> {
+        self.accounts.iter().find(|a| &a.account_id == account_id)
+    }
+
+    /// Get the valuation for the commodity type with the id `type_id`
+    /// held by the account with the given id.
+    pub fn get_holding(
+        &self,
+        account_id: &AccountID,
+        type_id: CommodityTypeID,
+    ) -> Option<&AccountValuation> {
+        self.accounts
+            .iter()
+            .find(|a| &a.account_id == account_id && a.balance.type_id == type_id)
+    }
+}
+
+/// Values commodities in the reporting commodity type, caching the
+/// [RateGraph](RateGraph) for each date.
+struct Valuer<'a, AT, ATV> {
+    program_state: &'a ProgramState<AT, ATV>,
+    reporting_commodity_type_id: CommodityTypeID,
+    lookup: PriceLookup,
+    rate_graphs: HashMap<NaiveDate, RateGraph>,
+}
+
+impl<'a, AT, ATV> Valuer<'a, AT, ATV> {
+    fn new(
+        program_state: &'a ProgramState<AT, ATV>,
+        reporting_commodity_type_id: CommodityTypeID,
+        lookup: PriceLookup,
+    ) -> Self {
+        Valuer {
+            program_state,
+            reporting_commodity_type_id,
+            lookup,
+            rate_graphs: HashMap::new(),
+        }
+    }
+
+    fn value(
+        &mut self,
+        commodity: Commodity,
+        date: NaiveDate,
+    ) -> Result<Commodity, AccountingError> {
+        if commodity.type_id == self.reporting_commodity_type_id {
+            return Ok(commodity);
+        }
+
+        if commodity.value.is_zero() {
+            return Ok(Commodity::zero(self.reporting_commodity_type_id));
+        }
+
+        let price_database = &self.program_state.price_database;
+        let lookup = self.lookup;
+        let rate_graph = self
+            .rate_graphs
+            .entry(date)
+            .or_insert_with(|| RateGraph::from_price_database(price_database, date, lookup));
+
+        match rate_graph.convert(
+            commodity,
+            self.reporting_commodity_type_id,
+            PathPreference::default(),
+        ) {
+            Ok(conversion) => Ok(conversion.to),
+            Err(AccountingError::NoConversionPath(commodity, type_id)) => {
+                Err(AccountingError::NoPriceAvailable(commodity, type_id, date))
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MarketValueReport;
+    use crate::{
+        Account, AccountStatus, ActionTypeValue, BookingMethod, Price, PriceLookup, Program,
+        ProgramState, Transaction, TransactionElement,
+    };
+    use chrono::NaiveDate;
+    use commodity::{Commodity, CommodityTypeID};
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    #[test]
+    fn market_value_report() {
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+
+        let brokerage = Rc::from(Account::new_with_id(Some("Brokerage"), aapl, None));
+        let equity = Rc::from(Account::new_with_id(Some("Equity"), aapl, None));
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
+        let date_3 = NaiveDate::from_ymd_opt(2020, 3, 1).unwrap();
+        let date_4 = NaiveDate::from_ymd_opt(2020, 4, 1).unwrap();
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(Price::new(aapl, Commodity::from_str("100 USD").unwrap(), date_1).into()),
+            Rc::new(Price::new(aapl, Commodity::from_str("200 USD").unwrap(), date_2).into()),
+            Rc::new(Price::new(aapl, Commodity::from_str("250 USD").unwrap(), date_3).into()),
+            Rc::new(
+                Transaction::new_simple(
+                    Some("Buy"),
+                    date_1,
+                    equity.id,
+                    brokerage.id,
+                    Commodity::from_str("10 AAPL").unwrap(),
+                    None,
+                )
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new_simple(
+                    Some("Buy"),
+                    date_2,
+                    equity.id,
+                    brokerage.id,
+                    Commodity::from_str("10 AAPL").unwrap(),
+                    None,
+                )
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new_simple(
+                    Some("Sell"),
+                    date_3,
+                    brokerage.id,
+                    equity.id,
+                    Commodity::from_str("5 AAPL").unwrap(),
+                    None,
+                )
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new_simple(
+                    Some("Sell"),
+                    date_4,
+                    brokerage.id,
+                    equity.id,
+                    Commodity::from_str("15 AAPL").unwrap(),
+                    None,
+                )
+                .into(),
+            ),
+        ];
+
+        let program = Program::new(actions);
+        let mut program_state =
+            ProgramState::new(&[brokerage.clone(), equity], AccountStatus::Open);
+        program_state.execute_program(&program).unwrap();
+
+        let report =
+            MarketValueReport::new(&program_state, usd, date_3, PriceLookup::LatestBefore).unwrap();
+
+        let valuation = report.get_account(&brokerage.id).unwrap();
+        assert_eq!(Commodity::from_str("15 AAPL").unwrap(), valuation.balance);
+        assert_eq!(
+            Commodity::from_str("2250 USD").unwrap(),
+            valuation.book_value
+        );
+        assert_eq!(
+            Commodity::from_str("3750 USD").unwrap(),
+            valuation.market_value
+        );
+        assert_eq!(
+            Commodity::from_str("1500 USD").unwrap(),
+            valuation.unrealized_gain
+        );
+        assert_eq!(
+            Commodity::from_str("0 USD").unwrap(),
+            report.total_unrealized_gain
+        );
+
+        let report =
+            MarketValueReport::new(&program_state, usd, date_4, PriceLookup::LatestBefore).unwrap();
+        let valuation = report.get_account(&brokerage.id).unwrap();
+        assert_eq!(Commodity::from_str("0 USD").unwrap(), valuation.book_value);
+        assert_eq!(
+            Commodity::from_str("0 USD").unwrap(),
+            valuation.market_value
+        );
+    }
+
+    #[test]
+    fn market_value_report_lots() {
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+
+        let brokerage = Rc::from(
+            Account::new_with_id(Some("Brokerage"), aapl, None)
+                .with_booking_method(BookingMethod::Fifo),
+        );
+        let cash = Rc::from(Account::new_with_id(Some("Cash"), usd, None));
+        let gains = Rc::from(Account::new_with_id(Some("Realized Gains"), usd, None));
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
+        let date_3 = NaiveDate::from_ymd_opt(2020, 3, 1).unwrap();
+
+        let trade = |amount: &str| -> TransactionElement {
+            TransactionElement::new(
+                brokerage.id,
+                Some(Commodity::from_str(amount).unwrap()),
+                None,
+            )
+        };
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(Price::new(aapl, Commodity::from_str("100 USD").unwrap(), date_1).into()),
+            Rc::new(Price::new(aapl, Commodity::from_str("200 USD").unwrap(), date_2).into()),
+            Rc::new(Price::new(aapl, Commodity::from_str("300 USD").unwrap(), date_3).into()),
+            Rc::new(
+                Transaction::new(
+                    Some("Buy"),
+                    date_1,
+                    vec![
+                        trade("10 AAPL").with_cost(Commodity::from_str("100 USD").unwrap()),
+                        TransactionElement::new(cash.id, None, None),
+                    ],
+                )
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new(
+                    Some("Buy"),
+                    date_2,
+                    vec![
+                        trade("10 AAPL").with_cost(Commodity::from_str("200 USD").unwrap()),
+                        TransactionElement::new(cash.id, None, None),
+                    ],
+                )
+                .into(),
+            ),
+            Rc::new(
+                Transaction::new(
+                    Some("Sell"),
+                    date_3,
+                    vec![
+                        trade("-10 AAPL").with_price(Commodity::from_str("300 USD").unwrap()),
+                        TransactionElement::new(cash.id, None, None),
+                    ],
+                )
+                .into(),
+            ),
+        ];
+
+        let program = Program::new(actions);
+        let mut program_state = ProgramState::new(
+            &[brokerage.clone(), cash, gains.clone()],
+            AccountStatus::Open,
+        );
+        program_state.realized_gains_account_id = Some(gains.id);
+        program_state.execute_program(&program).unwrap();
+
+        // the first lot was disposed of, so the remaining lot is
+        // valued at its cost (rather than the average cost)
+        let report =
+            MarketValueReport::new(&program_state, usd, date_3, PriceLookup::LatestBefore).unwrap();
+        let valuation = report.get_account(&brokerage.id).unwrap();
+        assert_eq!(Commodity::from_str("10 AAPL").unwrap(), valuation.balance);
+        assert_eq!(
+            Commodity::from_str("2000 USD").unwrap(),
+            valuation.book_value
+        );
+        assert_eq!(
+            Commodity::from_str("1000 USD").unwrap(),
+            valuation.unrealized_gain
+        );
+
+        // both lots were held before the sale
+        let report =
+            MarketValueReport::new(&program_state, usd, date_2, PriceLookup::LatestBefore).unwrap();
+        assert_eq!(
+            Commodity::from_str("3000 USD").unwrap(),
+            report.get_account(&brokerage.id).unwrap().book_value
+        );
+    }
+
+    #[test]
+    fn market_value_report_inventory() {
+        let aapl = CommodityTypeID::from_str("AAPL").unwrap();
+        let msft = CommodityTypeID::from_str("MSFT").unwrap();
+        let usd = CommodityTypeID::from_str("USD").unwrap();
+
+        let brokerage =
+            Rc::from(Account::new_with_id(Some("Brokerage"), usd, None).with_inventory());
+        let bank = Rc::from(Account::new_with_id(Some("Bank"), usd, None));
+
+        let date_1 = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let date_2 = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
+
+        let buy = |amount: &str, cost: &str, date| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new(
+                    Some("Buy"),
+                    date,
+                    vec![
+                        TransactionElement::new(
+                            brokerage.id,
+                            Some(Commodity::from_str(amount).unwrap()),
+                            None,
+                        )
+                        .with_cost(Commodity::from_str(cost).unwrap()),
+                        TransactionElement::new(bank.id, None, None),
+                    ],
+                )
+                .into(),
+            )
+        };
+        let sell = |amount: &str, price: &str, date| -> Rc<ActionTypeValue> {
+            Rc::new(
+                Transaction::new(
+                    Some("Sell"),
+                    date,
+                    vec![
+                        TransactionElement::new(
+                            brokerage.id,
+                            Some(Commodity::from_str(amount).unwrap().neg()),
+                            None,
+                        )
+                        .with_price(Commodity::from_str(price).unwrap()),
+                        TransactionElement::new(brokerage.id, None, None),
+                    ],
+                )
+                .into(),
+            )
+        };
+
+        let actions: Vec<Rc<ActionTypeValue>> = vec![
+            Rc::new(Price::new(aapl, Commodity::from_str("100 USD").unwrap(), date_1).into()),
+            Rc::new(Price::new(msft, Commodity::from_str("200 USD").unwrap(), date_1).into()),
+            Rc::new(Price::new(aapl, Commodity::from_str("150 USD").unwrap(), date_2).into()),
+            Rc::new(Price::new(msft, Commodity::from_str("300 USD").unwrap(), date_2).into()),
+            buy("10 AAPL", "100 USD", date_1),
+            buy("5 MSFT", "200 USD", date_1),
+            sell("5 MSFT", "300 USD", date_2),
+        ];
+
+        let program = Program::new(actions);
+        let mut program_state =
+            ProgramState::new(&[brokerage.clone(), bank.clone()], AccountStatus::Open);
+        program_state.execute_program(&program).unwrap();
+
+        let report =
+            MarketValueReport::new(&program_state, usd, date_1, PriceLookup::LatestBefore).unwrap();
+        let aapl_valuation = report.get_holding(&brokerage.id, aapl).unwrap();
+        assert_eq!(
+            Commodity::from_str("10 AAPL").unwrap(),
+            aapl_valuation.balance
+        );
+        assert_eq!(
+            Commodity::from_str("1000 USD").unwrap(),
+            aapl_valuation.book_value
+        );
+        // sold by the end of the program, but held on the date
+        let msft_valuation = report.get_holding(&brokerage.id, msft).unwrap();
+        assert_eq!(
+            Commodity::from_str("5 MSFT").unwrap(),
+            msft_valuation.balance
+        );
+        assert_eq!(
+            Commodity::from_str("2000 USD").unwrap(),
+            report.total_market_value
+        );
+
+        let report =
+            MarketValueReport::new(&program_state, usd, date_2, PriceLookup::LatestBefore).unwrap();
+        assert_eq!(2, report.accounts.len());
+        assert_eq!(
+            Commodity::from_str("1500 USD").unwrap(),
+            report
+                .get_holding(&brokerage.id, aapl)
+                .unwrap()
+                .market_value
+        );
+        assert_eq!(
+            Commodity::from_str("0 MSFT").unwrap(),
+            report.get_holding(&brokerage.id, msft).unwrap().balance
+        );
+        assert_eq!(
+            Commodity::from_str("500 USD").unwrap(),
+            report.total_unrealized_gain
+        );
+    }
+}